@@ -0,0 +1,153 @@
+// Copyright 2023-2023 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use crate::{DragOutcome, Error, Result};
+
+#[derive(Default)]
+struct Shared {
+    outcome: Option<DragOutcome>,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+/// Future resolving to the [`DragOutcome`] of a drag operation started with [`crate::start_drag_async`].
+///
+/// It does not depend on any async runtime. Resolves to [`Error::FailedToDrop`] if the drag ended without reporting an outcome.
+pub struct DragFuture {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Future for DragFuture {
+    type Output = Result<DragOutcome>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut shared = self.shared.lock().unwrap();
+        if let Some(outcome) = shared.outcome.take() {
+            Poll::Ready(Ok(outcome))
+        } else if shared.closed {
+            Poll::Ready(Err(Error::FailedToDrop))
+        } else {
+            shared.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// Sending half of a [`DragFuture`], moved into the `start_drag` callback.
+pub(crate) struct OutcomeSender {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl OutcomeSender {
+    pub(crate) fn send(&self, outcome: DragOutcome) {
+        let mut shared = self.shared.lock().unwrap();
+        if shared.outcome.is_none() && !shared.closed {
+            shared.outcome = Some(outcome);
+            shared.closed = true;
+            if let Some(waker) = shared.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl Drop for OutcomeSender {
+    fn drop(&mut self) {
+        let mut shared = self.shared.lock().unwrap();
+        if !shared.closed {
+            shared.closed = true;
+            if let Some(waker) = shared.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+pub(crate) fn channel() -> (OutcomeSender, DragFuture) {
+    let shared = Arc::new(Mutex::new(Shared::default()));
+    (
+        OutcomeSender {
+            shared: shared.clone(),
+        },
+        DragFuture { shared },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DragOperation, DragResult, Modifiers};
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        task::Wake,
+    };
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn poll(future: &mut DragFuture, waker: &Arc<CountingWaker>) -> Poll<Result<DragOutcome>> {
+        let waker = Waker::from(waker.clone());
+        Pin::new(future).poll(&mut Context::from_waker(&waker))
+    }
+
+    fn outcome() -> DragOutcome {
+        DragOutcome {
+            result: DragResult::Dropped(DragOperation::Copy),
+            cursor_position: None,
+            modifiers: Modifiers::default(),
+            duration: Default::default(),
+            promised_file_path: None,
+            destination: None,
+            drop_location: None,
+        }
+    }
+
+    #[test]
+    fn resolves_to_the_sent_outcome() {
+        let waker = Arc::new(CountingWaker::default());
+        let (sender, mut future) = channel();
+        assert!(poll(&mut future, &waker).is_pending());
+
+        sender.send(outcome());
+        assert_eq!(waker.0.load(Ordering::SeqCst), 1);
+        assert!(matches!(
+            poll(&mut future, &waker),
+            Poll::Ready(Ok(DragOutcome {
+                result: DragResult::Dropped(DragOperation::Copy),
+                ..
+            }))
+        ));
+
+        // dropping the sender once the outcome was sent must not wake again
+        drop(sender);
+        assert_eq!(waker.0.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn fails_when_the_sender_is_dropped() {
+        let waker = Arc::new(CountingWaker::default());
+        let (sender, mut future) = channel();
+        assert!(poll(&mut future, &waker).is_pending());
+
+        drop(sender);
+        assert_eq!(waker.0.load(Ordering::SeqCst), 1);
+        assert!(matches!(
+            poll(&mut future, &waker),
+            Poll::Ready(Err(Error::FailedToDrop))
+        ));
+    }
+}
@@ -2,20 +2,24 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-License-Identifier: MIT
 
-//!Start a drag operation out of a window on macOS, Windows and Linux (via GTK).
+//!Start a drag operation out of a window on macOS, Windows and Linux (via GTK, X11 or Wayland).
 //!
 //! Tested for [tao](https://github.com/tauri-apps/tao) (latest),
 //! [winit](https://github.com/rust-windowing/winit) (latest),
 //! [wry](https://github.com/tauri-apps/wry) (v0.24) and
 //! [tauri](https://github.com/tauri-apps/tauri) (v1) windows.
 //!
-//! Due to the GTK-based implementation, winit currently cannot leverage this crate on Linux yet.
+//! On Linux, windows that are not managed by GTK (e.g. winit windows) are supported through native
+//! XDND and Wayland data device implementations, enabled by the default `x11` and `wayland` cargo features.
+//! On Wayland, call `drag::prepare` once after creating such a window and keep the returned handle.
 //!
 //! - Add the `drag` dependency:
 //!
 //! `$ cargo add drag`
 //!
-//! - Use the `drag::start_drag` function. It takes a `&T: raw_window_handle::HasWindowHandle` type on macOS and Windows, and a `&gtk::ApplicationWindow` on Linux:
+//! - Use the `drag::start_drag` function. It takes a `&T: raw_window_handle::HasWindowHandle + raw_window_handle::HasDisplayHandle` type.
+//!   On Linux the handle must belong to a GTK window of this process (e.g. tao, wry or tauri windows)
+//!   or to an X11 or Wayland window, use `drag::start_drag_from_widget` to start the drag from a specific GTK widget instead:
 //!
 //! - tao:
 //!   ```rust,no_run
@@ -26,17 +30,11 @@
 //!   let preview_icon = drag::Image::File("./examples/icon.png".into());
 //!
 //!   drag::start_drag(
-//!     #[cfg(target_os = "linux")]
-//!     {
-//!       use tao::platform::unix::WindowExtUnix;
-//!       window.gtk_window()
-//!     },
-//!     #[cfg(not(target_os = "linux"))]
 //!     &window,
 //!     item,
 //!     preview_icon,
-//!     |result, cursor_position| {
-//!       println!("drag result: {result:?}");
+//!     |outcome| {
+//!       println!("drag outcome: {outcome:?}");
 //!     },
 //!     drag::Options::default(),
 //!   );
@@ -52,17 +50,11 @@
 //!   let preview_icon = drag::Image::File("./examples/icon.png".into());
 //!
 //!   drag::start_drag(
-//!     #[cfg(target_os = "linux")]
-//!     {
-//!       use tao::platform::unix::WindowExtUnix;
-//!       window.gtk_window()
-//!     },
-//!     #[cfg(not(target_os = "linux"))]
 //!     &window,
 //!     item,
 //!     preview_icon,
-//!     |result, cursor_position| {
-//!       println!("drag result: {result:?}");
+//!     |outcome| {
+//!       println!("drag outcome: {outcome:?}");
 //!     },
 //!     drag::Options::default(),
 //!   );
@@ -71,13 +63,15 @@
 //!   - winit:
 //!   ```rust,ignore
 //!   let window = ...winit window;
+//!   // required on Wayland, where the drag can only be started after a pointer button press was observed,
+//!   // keep the handle as long as the window
+//!   let prepared = drag::prepare(&window).unwrap();
 //!
 //!   let item = drag::DragItem::Files(vec![std::fs::canonicalize("./examples/icon.png").unwrap()]);
 //!   let preview_icon = drag::Image::File("./examples/icon.png".into());
 //!
-//!   # #[cfg(not(target_os = "linux"))]
-//!   let _ = drag::start_drag(&window, item, preview_icon, |result, cursor_position| {
-//!     println!("drag result: {result:?}");
+//!   let _ = drag::start_drag(&window, item, preview_icon, |outcome| {
+//!     println!("drag outcome: {outcome:?}");
 //!   }, Default::default());
 //!   ```
 
@@ -85,11 +79,123 @@
 #[macro_use]
 extern crate objc;
 
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
+mod future;
+mod local;
 mod platform_impl;
+mod validate;
 
+pub use future::DragFuture;
+/// Registers a window as a drop target, calling the handler with every [`DropEvent`] to decide whether the drop is accepted.
+///
+/// The handler answers each event with a [`DropResponse`]. Respond to [`DropEvent::Dropped`] with
+/// [`DropResponse::Fetch`] to receive the data of one of the offered types in a [`DropEvent::Data`] event.
+///
+/// - **Linux**: The handle must belong to a GTK window of this process, see [`register_drop_target_on_widget`].
+/// - **macOS / Windows**: Not supported, returns [`Error::UnsupportedOperation`].
+pub use platform_impl::register_drop_target;
+/// Registers a GTK widget as a drop target, like [`register_drop_target`] does for the GTK window matching a window handle.
+#[cfg(target_os = "linux")]
+pub use platform_impl::register_drop_target_on_widget;
+/// Starts a drag operation out of the given window, calling `on_drop_callback` with the [`DragOutcome`] once it ended.
+///
+/// - **Linux (x11, wayland)**: For windows that are not managed by GTK, the drag blocks the calling thread until it ends,
+///   so the event loop of the application does not run meanwhile. Other windows of the application cannot answer
+///   the drag, and a drop on them ends with [`CancelReason::NoTarget`].
 pub use platform_impl::start_drag;
+/// Starts a drag operation from the given GTK widget, like [`start_drag`] does for the GTK window matching a window handle.
+///
+/// Use it to start the drag from the widget the user pressed on, e.g. a `WebKitWebView` inside a plain `gtk::Window`.
+#[cfg(target_os = "linux")]
+pub use platform_impl::start_drag_from_widget;
+
+/// Starts a drag operation like [`start_drag_from_widget`], returning a [`DragFuture`] that resolves to the [`DragOutcome`] instead of calling a closure.
+#[cfg(target_os = "linux")]
+pub fn start_drag_from_widget_async<W: gtk::prelude::IsA<gtk::Widget>>(
+    widget: &W,
+    item: DragItem,
+    image: Image,
+    options: Options,
+) -> Result<DragFuture> {
+    let (sender, future) = future::channel();
+    start_drag_from_widget(
+        widget,
+        item,
+        image,
+        move |outcome| sender.send(outcome),
+        options,
+    )?;
+    Ok(future)
+}
+
+/// Starts a drag operation like [`start_drag`], returning a [`DragFuture`] that resolves to the [`DragOutcome`] instead of calling a closure.
+///
+/// ```rust,ignore
+/// let outcome = drag::start_drag_async(&window, item, preview_icon, Default::default())?.await?;
+/// println!("drag outcome: {outcome:?}");
+/// ```
+pub fn start_drag_async<
+    W: raw_window_handle::HasWindowHandle + raw_window_handle::HasDisplayHandle,
+>(
+    window: &W,
+    item: DragItem,
+    image: Image,
+    options: Options,
+) -> Result<DragFuture> {
+    let (sender, future) = future::channel();
+    start_drag(
+        window,
+        item,
+        image,
+        move |outcome| sender.send(outcome),
+        options,
+    )?;
+    Ok(future)
+}
+
+/// Takes the [`Options::payload`] of the drag started by this process, if that drag is in progress.
+///
+/// Call it from a [`register_drop_target`] handler on [`DropEvent::Dropped`] to receive the payload
+/// of a drag between two windows of this process, see [`DropEvent::Entered::local_payload`].
+/// The payload can only be taken once, and is dropped when the drag ends otherwise.
+///
+/// - **Windows / Linux (x11, wayland)**: The drag ends when `start_drag` returns, so events delivered
+///   later through the application event loop (e.g. Tauri's drag drop event) cannot take the payload.
+pub fn take_local_payload() -> Option<Box<dyn std::any::Any + Send>> {
+    local::take()
+}
+
+/// Prepares drag operations out of the given window, to be called once after creating it.
+///
+/// Keep the returned [`PreparedWindow`] as long as the window.
+///
+/// - **Linux (wayland)**: Needed for windows that are not managed by GTK. The compositor only starts a drag
+///   with the serial of the pointer button press, and presses are only observed while a [`PreparedWindow`]
+///   of the display is alive, so without it [`start_drag`] fails with [`Error::FailedToStartDrag`].
+///   Drop it before the Wayland display connection is closed.
+/// - **macOS / Windows / Linux (gtk, x11)**: Does nothing.
+pub fn prepare<W: raw_window_handle::HasWindowHandle + raw_window_handle::HasDisplayHandle>(
+    window: &W,
+) -> Result<PreparedWindow> {
+    #[cfg(target_os = "linux")]
+    return platform_impl::prepare(window).map(|inner| PreparedWindow { _inner: inner });
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = window;
+        Ok(PreparedWindow {})
+    }
+}
+
+/// Handle returned by [`prepare`], dropping it stops preparing drag operations out of the window.
+#[must_use = "drag operations out of the window are only prepared while the handle is alive"]
+pub struct PreparedWindow {
+    #[cfg(target_os = "linux")]
+    _inner: platform_impl::PreparedWindow,
+}
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -98,6 +204,12 @@ pub enum Error {
     #[cfg(windows)]
     #[error("{0}")]
     WindowsError(#[from] windows::core::Error),
+    #[cfg(all(target_os = "linux", feature = "x11"))]
+    #[error("{0}")]
+    X11Error(#[from] x11rb::errors::ReplyOrIdError),
+    #[cfg(all(target_os = "linux", feature = "wayland"))]
+    #[error("{0}")]
+    WaylandError(#[from] wayland_client::DispatchError),
     #[error(transparent)]
     Io(#[from] std::io::Error),
     #[error("unsupported window handle")]
@@ -113,17 +225,250 @@ pub enum Error {
     FailedToDrop,
     #[error("failed to get cursor position")]
     FailedToGetCursorPosition,
+    #[error("failed to decode the drag image")]
+    InvalidImage,
+    #[error("operation not supported on this platform")]
+    UnsupportedOperation,
+    #[error("file not found: {}", .0.display())]
+    FileNotFound(PathBuf),
+    #[error("path is not absolute: {}", .0.display())]
+    RelativePath(PathBuf),
+    #[error("file is not readable: {}", .0.display())]
+    UnreadableFile(PathBuf),
+    #[error("no data type to offer")]
+    EmptyTypeList,
+}
+
+/// Handle to a drag operation started with [`start_drag`].
+///
+/// Dropping the session does not cancel the drag operation.
+///
+/// Once the drag operation ended, [`DragSession::cancel`] and [`DragSession::set_image`] do nothing and return `Ok(())`.
+/// While it is still in flight, they return [`Error::UnsupportedOperation`] if the platform cannot perform them.
+pub struct DragSession {
+    inner: platform_impl::DragSession,
+}
+
+impl DragSession {
+    /// Cancels the drag operation, reporting [`CancelReason::Programmatic`] to the `start_drag` callback.
+    ///
+    /// Does nothing if the drag operation already ended.
+    ///
+    /// - **macOS**: Not supported, returns [`Error::UnsupportedOperation`] while the drag operation is in flight.
+    /// - **Windows / Linux (x11, wayland)**: The drag operation always ended when `start_drag` returns, so this does nothing.
+    pub fn cancel(&self) -> Result<()> {
+        self.inner.cancel()
+    }
+
+    /// Whether the drag operation is still in flight.
+    ///
+    /// - **Windows / Linux (x11, wayland)**: Always `false`, the drag operation ended when `start_drag` returns.
+    pub fn is_active(&self) -> bool {
+        self.inner.is_active()
+    }
+
+    /// Replaces the image shown under the cursor.
+    ///
+    /// Does nothing if the drag operation already ended.
+    ///
+    /// - **macOS**: Not supported, returns [`Error::UnsupportedOperation`] while the drag operation is in flight.
+    /// - **Windows / Linux (x11, wayland)**: The drag operation always ended when `start_drag` returns, so this does nothing.
+    pub fn set_image(&self, image: Image) -> Result<()> {
+        self.inner.set_image(image)
+    }
+}
+
+/// Handle to a drop target registered with [`register_drop_target`].
+///
+/// Dropping the handle does not unregister the drop target.
+pub struct DropTarget {
+    inner: platform_impl::DropTarget,
+}
+
+impl DropTarget {
+    /// Stops receiving drops.
+    pub fn unregister(self) -> Result<()> {
+        self.inner.unregister()
+    }
+}
+
+/// Progress of a drag operation over a drop target, reported to the [`register_drop_target`] handler.
+///
+/// Positions are relative to the drop target.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum DropEvent {
+    /// A drag entered the drop target.
+    Entered {
+        /// The MIME types offered by the drag source.
+        types: Vec<String>,
+        /// The operations allowed by the drag source.
+        operations: Vec<DragOperation>,
+        /// Whether the drag was started by this process with an [`Options::payload`], see [`take_local_payload`].
+        local_payload: bool,
+        position: CursorPosition,
+    },
+    /// The drag moved over the drop target.
+    Moved { position: CursorPosition },
+    /// The drag left the drop target without dropping.
+    Left,
+    /// The item was dropped on the drop target.
+    Dropped { position: CursorPosition },
+    /// The data requested with [`DropResponse::Fetch`], `None` if the drag source could not provide it.
+    Data {
+        mime_type: String,
+        data: Option<Vec<u8>>,
+    },
+}
+
+/// The answer of a drop target handler to a [`DropEvent`].
+///
+/// The answer to [`DropEvent::Left`] and [`DropEvent::Data`] is ignored.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum DropResponse {
+    /// Refuse the drop.
+    Reject,
+    /// Accept the drop with the given operation.
+    Accept(DragOperation),
+    /// Accept the drop with the given operation and fetch the data of the given type once dropped.
+    Fetch {
+        operation: DragOperation,
+        mime_type: String,
+    },
+}
+
+/// The outcome of a drag operation, passed to the `start_drag` callback.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct DragOutcome {
+    /// Whether the item was dropped or the drag was cancelled.
+    pub result: DragResult,
+    /// Position of the cursor when the drag ended, `None` if it could not be determined (e.g. without a pointer device).
+    ///
+    /// - **Linux (wayland)**: Always `None`, clients cannot query the global cursor position.
+    pub cursor_position: Option<CursorPosition>,
+    /// Modifier keys held when the drag ended.
+    ///
+    /// - **Linux (wayland)**: Not supported.
+    pub modifiers: Modifiers,
+    /// Time elapsed between starting and ending the drag.
+    pub duration: Duration,
+    /// Where the [`DragItem::PromisedFile`] was written, if the drop target asked for it.
+    pub promised_file_path: Option<PathBuf>,
+    /// The kind of window under the cursor when the drag ended, whether it accepted the drop or not.
+    /// `None` if it could not be determined.
+    ///
+    /// - **Linux (wayland)**: Always `None`, clients cannot find out where a drag ended.
+    pub destination: Option<DropDestination>,
+    /// Where the cursor was when the drag ended, in physical and logical pixels, `None` if it could not be determined.
+    ///
+    /// - **Linux (wayland)**: Always `None`, clients cannot query the global cursor position.
+    pub drop_location: Option<DropLocation>,
+}
+
+/// The kind of window under the cursor when a drag ended, see [`DragOutcome::destination`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum DropDestination {
+    /// No window, e.g. the empty desktop.
+    Desktop,
+    /// A window of this process.
+    OwnWindow {
+        /// The native id of the toplevel window: the `HWND` on Windows, the window number on macOS and the X11 window id on Linux.
+        ///
+        /// - **Linux (gtk)**: `None` on Wayland.
+        window_id: Option<u64>,
+    },
+    /// A window of another application.
+    Foreign {
+        /// The native id of the toplevel window, like [`DropDestination::OwnWindow`].
+        ///
+        /// - **Linux (gtk)**: `None` on Wayland.
+        window_id: Option<u64>,
+        /// The class of the application owning the window: the `WM_CLASS` class on Linux,
+        /// the bundle identifier on macOS and the window class name on Windows.
+        ///
+        /// - **Linux (gtk)**: `None` on Wayland or without the `x11` cargo feature.
+        app_class: Option<String>,
+        /// The id of the process owning the window.
+        ///
+        /// - **Linux**: Read from `_NET_WM_PID`, which is set by the application itself and may be missing.
+        /// - **Linux (gtk)**: `None` on Wayland or without the `x11` cargo feature.
+        pid: Option<u32>,
+    },
 }
 
 #[derive(Debug, Clone, Copy)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 pub enum DragResult {
-    Dropped,
-    Cancel,
+    /// The item was dropped and the target performed the given operation.
+    Dropped(DragOperation),
+    /// The drag was cancelled for the given reason.
+    Cancel(CancelReason),
+}
+
+/// Why a drag operation was cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum CancelReason {
+    /// The item was dropped where no target accepted it, e.g. on the empty desktop.
+    NoTarget,
+    /// The user cancelled the drag, e.g. by pressing Escape.
+    ///
+    /// - **Linux (wayland)**: Not supported, reported as [`CancelReason::NoTarget`].
+    UserCancelled,
+    /// The drop target did not respond in time.
+    ///
+    /// - **macOS / Windows / Linux (wayland)**: Not supported.
+    TimeoutExpired,
+    /// The pointer grab was taken away from the drag.
+    ///
+    /// - **macOS / Windows / Linux (x11, wayland)**: Not supported.
+    GrabBroken,
+    /// The drag was cancelled with [`DragSession::cancel`].
+    Programmatic,
+    /// The drag failed for an unspecified reason.
+    Error,
+}
+
+/// Modifier keys held at the end of a drag operation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct Modifiers {
+    pub shift: bool,
+    pub control: bool,
+    /// The Alt key, or Option on macOS.
+    pub alt: bool,
+    /// The Super key, or Command on macOS.
+    ///
+    /// - **Windows**: Not supported.
+    pub meta: bool,
+}
+
+/// An operation the drop target can perform with the dragged item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum DragOperation {
+    /// Copy the item.
+    Copy,
+    /// Move the item, meaning the source is expected to delete it.
+    Move,
+    /// Create a link to the item.
+    ///
+    /// - **Linux (wayland)**: Not supported.
+    Link,
+    /// Let the target ask the user which operation should be performed.
+    ///
+    /// - **macOS / Windows**: Not supported. Ignored when negotiating the operation.
+    Ask,
 }
 
 pub type DataProvider = Box<dyn Fn(&str) -> Option<Vec<u8>>>;
 
+/// Writes a [`DragItem::PromisedFile`] to the path chosen by the drop target.
+pub type FileWriter = Box<dyn Fn(&Path) -> std::io::Result<()>>;
+
 /// Item to be dragged.
 pub enum DragItem {
     /// A list of files to be dragged.
@@ -132,17 +477,144 @@ pub enum DragItem {
     Files(Vec<PathBuf>),
     /// Data to share with another app.
     ///
+    /// Each entry of `types` is offered to the drop target, and the `provider` is called with the type the target requested.
+    ///
     /// - **Windows**: Not supported. Will result in a dummy drag operation of current folder that will be cancelled upon dropping.
-    /// - **Linux (gtk)**: Not supported. Will result in a dummy drag operation that contains nothing to drop.
+    /// - **Linux (gtk)**: Each type is registered as a GTK target and the `provider` is called on `drag-data-get`.
     Data {
         provider: DataProvider,
         types: Vec<String>,
     },
+    /// Several representations of the same content offered in a single drag,
+    /// letting the drop target pick the one it understands.
+    ///
+    /// - **Windows**: Only the `files` are offered. Without files, will result in the same dummy drag operation as [`DragItem::Data`].
+    /// - **macOS**: Each file is dragged as its own item, alongside one item holding the `data` and `types`.
+    /// - **Linux (gtk)**: All representations are registered in one GTK target list.
+    Mixed {
+        /// Files offered as `text/uri-list`. The paths must be absolute.
+        files: Vec<PathBuf>,
+        /// Payloads known upfront, as `(type, bytes)` pairs.
+        data: Vec<(String, Vec<u8>)>,
+        /// Called with the requested type for each entry of `types`.
+        provider: Option<DataProvider>,
+        /// Types whose data is only produced when the drop target requests it.
+        types: Vec<String>,
+    },
+    /// Plain text.
+    ///
+    /// - **Windows**: Not supported. Will result in the same dummy drag operation as [`DragItem::Data`].
+    /// - **macOS**: Offered as `public.utf8-plain-text`.
+    /// - **Linux (gtk)**: Offered as `text/plain;charset=utf-8`, `UTF8_STRING`, `STRING`, `TEXT` and `text/plain`.
+    Text(String),
+    /// HTML markup, with an optional plain text representation for targets that do not understand HTML.
+    ///
+    /// - **Windows**: Not supported. Will result in the same dummy drag operation as [`DragItem::Data`].
+    /// - **macOS**: Offered as `public.html`, and `public.utf8-plain-text` for the fallback.
+    /// - **Linux (gtk)**: Offered as `text/html`, and the same targets as [`DragItem::Text`] for the fallback.
+    Html {
+        html: String,
+        plain_fallback: Option<String>,
+    },
+    /// A link, with an optional title used by browsers for bookmarks.
+    ///
+    /// - **Windows**: Not supported. Will result in the same dummy drag operation as [`DragItem::Data`].
+    /// - **macOS**: Offered as `public.url`, `public.url-name` and `public.utf8-plain-text`.
+    /// - **Linux (gtk)**: Offered as `text/uri-list`, `_NETSCAPE_URL`, `text/x-moz-url` and the same targets as [`DragItem::Text`].
+    Url { url: String, title: Option<String> },
+    /// A file that is only written once dropped, at the location chosen by the drop target.
+    ///
+    /// The `writer` is called with the final path, which is then reported in [`DragOutcome::promised_file_path`].
+    ///
+    /// - **Windows**: Not supported. Will result in the same dummy drag operation as [`DragItem::Data`].
+    /// - **macOS**: Not supported, returns [`Error::UnsupportedOperation`].
+    /// - **Linux (gtk)**: Uses the XDND Direct Save protocol (`XdndDirectSave0`) supported by most file managers.
+    ///   The outcome is reported once the drop target finished, after the file was written.
+    /// - **Linux (x11, wayland)**: Not supported, returns [`Error::UnsupportedOperation`].
+    PromisedFile {
+        /// File name suggested to the drop target.
+        file_name: String,
+        writer: FileWriter,
+    },
 }
 
-#[derive(Default)]
 pub struct Options {
     pub skip_animatation_on_cancel_or_failure: bool,
+    /// Operations the drop target is allowed to choose from. Defaults to [`DragOperation::Copy`] only.
+    pub allowed_operations: Vec<DragOperation>,
+    /// Called once the drop target confirmed a [`DragOperation::Move`], meaning the dragged item can be safely deleted.
+    ///
+    /// - **Linux (gtk)**: Called on the source `drag-data-delete` signal.
+    /// - **macOS / Windows / Linux (x11, wayland)**: Called after the drag session ended with a move operation.
+    pub on_move_completed: Option<Box<dyn Fn() + Send>>,
+    /// Called with the progress of the drag operation, see [`DragEvent`].
+    pub on_event: Option<Box<dyn Fn(DragEvent) + Send>>,
+    /// A Rust value handed as is to a drop target of this process, see [`take_local_payload`].
+    ///
+    /// Drop targets of other applications only see the [`DragItem`].
+    pub payload: Option<Box<dyn std::any::Any + Send>>,
+    /// The point of the drag image held under the cursor, see [`ImageAnchor`].
+    ///
+    /// Defaults to the top-left corner of the image, except on macOS where the image is centered on the cursor.
+    pub image_anchor: Option<ImageAnchor>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            skip_animatation_on_cancel_or_failure: false,
+            allowed_operations: vec![DragOperation::Copy],
+            on_move_completed: None,
+            on_event: None,
+            payload: None,
+            image_anchor: None,
+        }
+    }
+}
+
+/// Minimum interval between two [`DragEvent::Moved`] events.
+const MOVED_EVENT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Progress of a drag operation, reported to [`Options::on_event`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum DragEvent {
+    /// The drag operation started.
+    Started,
+    /// The cursor moved. Reported at most every 50 milliseconds.
+    ///
+    /// - **Linux (wayland)**: Not supported.
+    Moved(CursorPosition),
+    /// The cursor entered a window that might accept the drop.
+    ///
+    /// - **macOS / Windows / Linux (wayland)**: Not supported.
+    Entered,
+    /// The cursor left the window it previously entered.
+    ///
+    /// - **macOS / Windows / Linux (wayland)**: Not supported.
+    Left,
+    /// The operation the drop target accepts changed, `None` if the target does not accept the drop.
+    ///
+    /// - **macOS**: Not supported.
+    ActionChanged(Option<DragOperation>),
+}
+
+/// Wraps the [`Options::on_event`] handler to drop [`DragEvent::Moved`] events reported too frequently.
+#[cfg(any(not(target_os = "linux"), feature = "x11"))]
+pub(crate) fn throttle_events(
+    handler: Box<dyn Fn(DragEvent) + Send>,
+) -> Box<dyn Fn(DragEvent) + Send> {
+    let last_moved = std::sync::Mutex::new(None::<std::time::Instant>);
+    Box::new(move |event| {
+        if let DragEvent::Moved(_) = event {
+            let mut last_moved = last_moved.lock().unwrap();
+            if last_moved.is_some_and(|instant| instant.elapsed() < MOVED_EVENT_INTERVAL) {
+                return;
+            }
+            *last_moved = Some(std::time::Instant::now());
+        }
+        handler(event)
+    })
 }
 
 /// An image definition.
@@ -154,10 +626,170 @@ pub enum Image {
     File(PathBuf),
     /// Raw bytes of the image.
     Raw(Vec<u8>),
+    /// Decoded pixels, 4 bytes per pixel in RGBA order without premultiplied alpha,
+    /// row by row from the top-left corner.
+    ///
+    /// With the `image` cargo feature, it can be converted from an `image::RgbaImage` or `image::DynamicImage`.
+    Rgba {
+        width: u32,
+        height: u32,
+        pixels: Vec<u8>,
+    },
+}
+
+#[cfg(feature = "image")]
+impl From<image::RgbaImage> for Image {
+    fn from(image: image::RgbaImage) -> Self {
+        Self::Rgba {
+            width: image.width(),
+            height: image.height(),
+            pixels: image.into_raw(),
+        }
+    }
+}
+
+#[cfg(feature = "image")]
+impl From<image::DynamicImage> for Image {
+    fn from(image: image::DynamicImage) -> Self {
+        image.into_rgba8().into()
+    }
+}
+
+/// The point of the drag image held under the cursor, see [`Options::image_anchor`].
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum ImageAnchor {
+    /// An offset from the top-left corner of the image, in image pixels.
+    ///
+    /// Use it to keep the preview of a dragged element under the point where the user grabbed it.
+    Offset { x: f64, y: f64 },
+    /// A point relative to the image size, from `0.0` to `1.0`: `(0.0, 0.0)` is the top-left corner
+    /// and `(0.5, 0.5)` the center of the image.
+    Relative { x: f64, y: f64 },
+}
+
+impl ImageAnchor {
+    /// The image centered on the cursor.
+    pub const CENTER: Self = Self::Relative { x: 0.5, y: 0.5 };
+
+    /// Returns the offset of the anchor from the top-left corner of an image of the given size.
+    pub(crate) fn offset(&self, width: f64, height: f64) -> (f64, f64) {
+        match *self {
+            Self::Offset { x, y } => (x, y),
+            Self::Relative { x, y } => (x * width, y * height),
+        }
+    }
+}
+
+/// Where the cursor was when a drag ended, see [`DragOutcome::drop_location`].
+///
+/// The logical position is the physical position divided by the scale factor of the monitor it lies on,
+/// so a window placed at the logical position on that monitor opens under the cursor.
+///
+/// - **macOS / Linux (gtk)**: Positions are reported in logical pixels, the physical position is the logical position multiplied by the scale factor.
+/// - **Linux (x11)**: The scale factor is read from the `Xft.dpi` resource, `1.0` if it is not set.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct DropLocation {
+    /// Position on the screen in physical pixels.
+    pub physical_position: PhysicalPosition,
+    /// Position on the screen in logical pixels.
+    pub logical_position: LogicalPosition,
+    /// The monitor the position lies on, `None` if it could not be determined.
+    pub monitor: Option<MonitorInfo>,
+    /// Position relative to the top-left corner of the source window content in logical pixels, `None` if it could not be determined.
+    pub window_position: Option<LogicalPosition>,
+}
+
+impl DropLocation {
+    /// Builds the location from the physical position reported by Windows and X11.
+    #[cfg(any(target_os = "windows", all(target_os = "linux", feature = "x11")))]
+    pub(crate) fn from_physical(
+        position: PhysicalPosition,
+        window_position: Option<PhysicalPosition>,
+        monitor: Option<MonitorInfo>,
+    ) -> Self {
+        let scale_factor = monitor.as_ref().map_or(1.0, |monitor| monitor.scale_factor);
+        Self {
+            logical_position: position.to_logical(scale_factor),
+            physical_position: position,
+            monitor,
+            window_position: window_position.map(|position| position.to_logical(scale_factor)),
+        }
+    }
+
+    /// Builds the location from the logical position reported by macOS and GTK.
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
+    pub(crate) fn from_logical(
+        position: LogicalPosition,
+        window_position: Option<LogicalPosition>,
+        monitor: Option<MonitorInfo>,
+    ) -> Self {
+        let scale_factor = monitor.as_ref().map_or(1.0, |monitor| monitor.scale_factor);
+        Self {
+            physical_position: PhysicalPosition {
+                x: (position.x * scale_factor).round() as i32,
+                y: (position.y * scale_factor).round() as i32,
+            },
+            logical_position: position,
+            monitor,
+            window_position,
+        }
+    }
+}
+
+/// A monitor, see [`DropLocation::monitor`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct MonitorInfo {
+    /// The name of the monitor, if available.
+    pub name: Option<String>,
+    /// Position of the top-left corner of the monitor on the screen in physical pixels.
+    pub position: PhysicalPosition,
+    /// Size of the monitor in physical pixels.
+    pub size: PhysicalSize,
+    /// The ratio between physical and logical pixels on this monitor.
+    pub scale_factor: f64,
+}
+
+/// A position in physical pixels.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct PhysicalPosition {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl PhysicalPosition {
+    #[cfg(any(target_os = "windows", all(target_os = "linux", feature = "x11")))]
+    fn to_logical(self, scale_factor: f64) -> LogicalPosition {
+        LogicalPosition {
+            x: f64::from(self.x) / scale_factor,
+            y: f64::from(self.y) / scale_factor,
+        }
+    }
+}
+
+/// A position in logical pixels.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct LogicalPosition {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// A size in physical pixels.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct PhysicalSize {
+    pub width: u32,
+    pub height: u32,
 }
 
 /// Logical position of the cursor.
 ///
+/// See [`DropLocation`] for the position of a drop in both physical and logical pixels.
+///
 /// - **Windows**: Currently the win32 API for logical position reports physical position as well, due to the complicated nature of potential multiple monitor with different scaling there's no trivial solution to be incorporated.
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
@@ -0,0 +1,83 @@
+// Copyright 2023-2023 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+use std::{
+    any::Any,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+/// The payload of the drag started by this process, with the id of that drag.
+///
+/// A pointer carries a single drag at a time, so while a payload is stored,
+/// any drop on a window of this process comes from the drag that stored it.
+static PAYLOAD: Mutex<Option<(u64, Box<dyn Any + Send>)>> = Mutex::new(None);
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Stores the [`crate::Options::payload`] of a drag that is starting, until the returned guard is dropped.
+///
+/// Backends keep the guard until the drag ended, once a drop target of this process had the chance to take the payload.
+/// It is also dropped if the drag fails to start.
+pub(crate) fn attach(payload: Option<Box<dyn Any + Send>>) -> Option<Release> {
+    payload.map(|payload| {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let _previous = PAYLOAD.lock().unwrap().replace((id, payload));
+        Release(id)
+    })
+}
+
+pub(crate) fn is_available() -> bool {
+    PAYLOAD.lock().unwrap().is_some()
+}
+
+pub(crate) fn take() -> Option<Box<dyn Any + Send>> {
+    PAYLOAD.lock().unwrap().take().map(|(_, payload)| payload)
+}
+
+/// Releases the payload stored by [`attach`] when dropped.
+pub(crate) struct Release(u64);
+
+impl Drop for Release {
+    fn drop(&mut self) {
+        // the payload may be anything, so it is dropped without holding the lock
+        let _payload = {
+            let mut payload = PAYLOAD.lock().unwrap();
+            // a newer drag may have replaced the payload already
+            if payload.as_ref().is_some_and(|(id, _)| *id == self.0) {
+                payload.take()
+            } else {
+                None
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a single test, as the payload is global to the process
+    #[test]
+    fn payload_is_released_with_the_guard() {
+        let release = attach(Some(Box::new(42u32)));
+        assert!(is_available());
+        drop(release);
+        assert!(!is_available());
+
+        let release = attach(Some(Box::new(42u32)));
+        let payload = take().and_then(|payload| payload.downcast::<u32>().ok());
+        assert_eq!(payload.as_deref(), Some(&42));
+        drop(release);
+
+        // a newer drag keeps its payload when the guard of the previous one is dropped
+        let previous = attach(Some(Box::new(1u32)));
+        let release = attach(Some(Box::new(2u32)));
+        drop(previous);
+        assert!(is_available());
+        drop(release);
+        assert!(!is_available());
+    }
+}
@@ -0,0 +1,185 @@
+// Copyright 2023-2023 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use gdkx11::{
+    gdk,
+    glib::{self, ObjectExt, SignalHandlerId, SourceId},
+};
+use gtk::prelude::{Cast, DragContextExtManual, IsA, WidgetExt, WidgetExtManual};
+use raw_window_handle::HasWindowHandle;
+
+use super::{drag_actions, drag_operation, find_gtk_window};
+use crate::{CursorPosition, DragOperation, DropEvent, DropResponse, Error};
+
+pub struct DropTarget {
+    widget: gtk::Widget,
+    handler_ids: Vec<SignalHandlerId>,
+}
+
+impl DropTarget {
+    pub fn unregister(self) -> crate::Result<()> {
+        for handler_id in self.handler_ids {
+            self.widget.disconnect(handler_id);
+        }
+        self.widget.drag_dest_unset();
+        Ok(())
+    }
+}
+
+pub fn register_drop_target<W: HasWindowHandle, F: Fn(DropEvent) -> DropResponse + 'static>(
+    handle: &W,
+    handler: F,
+) -> crate::Result<crate::DropTarget> {
+    let raw_handle = handle
+        .window_handle()
+        .map_err(|_| Error::UnsupportedWindowHandle)?
+        .as_raw();
+    let window = find_gtk_window(raw_handle).ok_or(Error::UnsupportedWindowHandle)?;
+    register_drop_target_on_widget(&window, handler)
+}
+
+pub fn register_drop_target_on_widget<
+    W: IsA<gtk::Widget>,
+    F: Fn(DropEvent) -> DropResponse + 'static,
+>(
+    widget: &W,
+    handler: F,
+) -> crate::Result<crate::DropTarget> {
+    let widget = widget.upcast_ref::<gtk::Widget>().clone();
+    // no default behavior and no target list, so every drag reaches the handler
+    widget.drag_dest_set(
+        gtk::DestDefaults::empty(),
+        &[],
+        drag_actions(&[
+            DragOperation::Copy,
+            DragOperation::Move,
+            DragOperation::Link,
+            DragOperation::Ask,
+        ]),
+    );
+
+    let handler = Rc::new(handler);
+    let entered = Rc::new(Cell::new(false));
+    let pending_leave: Rc<Cell<Option<SourceId>>> = Rc::new(Cell::new(None));
+    let pending_fetch: Rc<RefCell<Option<(DragOperation, String)>>> = Rc::new(RefCell::new(None));
+    let mut handler_ids = Vec::new();
+
+    let handler_ = handler.clone();
+    let entered_ = entered.clone();
+    handler_ids.push(
+        widget.connect_drag_motion(move |_, drag_context, x, y, time| {
+            let position = CursorPosition { x, y };
+            let event = if entered_.replace(true) {
+                DropEvent::Moved { position }
+            } else {
+                DropEvent::Entered {
+                    types: drag_context
+                        .list_targets()
+                        .iter()
+                        .map(|target| target.name().to_string())
+                        .collect(),
+                    operations: drag_operations(drag_context.actions()),
+                    local_payload: crate::local::is_available(),
+                    position,
+                }
+            };
+            let action = match handler_(event) {
+                DropResponse::Reject => gdk::DragAction::empty(),
+                DropResponse::Accept(operation) | DropResponse::Fetch { operation, .. } => {
+                    drag_actions(&[operation])
+                }
+            };
+            drag_context.drag_status(action, time);
+            true
+        }),
+    );
+
+    // GTK also emits `drag-leave` right before `drag-drop`,
+    // so the leave is only reported if no drop follows in the same main loop iteration
+    let handler_ = handler.clone();
+    let entered_ = entered.clone();
+    let pending_leave_ = pending_leave.clone();
+    handler_ids.push(widget.connect_drag_leave(move |_, _, _| {
+        let handler = handler_.clone();
+        let entered = entered_.clone();
+        let pending_leave = pending_leave_.clone();
+        pending_leave_.set(Some(glib::idle_add_local_once(move || {
+            pending_leave.set(None);
+            entered.set(false);
+            handler(DropEvent::Left);
+        })));
+    }));
+
+    let handler_ = handler.clone();
+    let pending_fetch_ = pending_fetch.clone();
+    handler_ids.push(
+        widget.connect_drag_drop(move |widget, drag_context, x, y, time| {
+            if let Some(source_id) = pending_leave.take() {
+                source_id.remove();
+            }
+            entered.set(false);
+
+            match handler_(DropEvent::Dropped {
+                position: CursorPosition { x, y },
+            }) {
+                DropResponse::Reject => drag_context.drag_finish(false, false, time),
+                DropResponse::Accept(operation) => {
+                    drag_context.drag_finish(true, operation == DragOperation::Move, time)
+                }
+                DropResponse::Fetch {
+                    operation,
+                    mime_type,
+                } => {
+                    // the drop is finished once the data was received, which can happen
+                    // within `drag_get_data` for a source of this process
+                    let target = gdk::Atom::intern(&mime_type);
+                    pending_fetch_.replace(Some((operation, mime_type)));
+                    widget.drag_get_data(drag_context, &target, time);
+                }
+            }
+            true
+        }),
+    );
+
+    handler_ids.push(widget.connect_drag_data_received(
+        move |_, drag_context, _, _, data, _, time| {
+            if let Some((operation, mime_type)) = pending_fetch.take() {
+                // a negative length means the source could not provide the data
+                let data = (data.length() >= 0).then(|| data.data());
+                let success = data.is_some();
+                handler(DropEvent::Data { mime_type, data });
+                drag_context.drag_finish(
+                    success,
+                    success && operation == DragOperation::Move,
+                    time,
+                );
+            }
+        },
+    ));
+
+    Ok(crate::DropTarget {
+        inner: DropTarget {
+            widget,
+            handler_ids,
+        },
+    })
+}
+
+fn drag_operations(actions: gdk::DragAction) -> Vec<DragOperation> {
+    [
+        gdk::DragAction::COPY,
+        gdk::DragAction::MOVE,
+        gdk::DragAction::LINK,
+        gdk::DragAction::ASK,
+    ]
+    .into_iter()
+    .filter(|action| actions.contains(*action))
+    .map(drag_operation)
+    .collect()
+}
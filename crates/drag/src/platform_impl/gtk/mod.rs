@@ -2,85 +2,527 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-License-Identifier: MIT
 
-use crate::{CursorPosition, DragItem, DragResult, Error, Image, Options};
+use crate::{
+    CancelReason, CursorPosition, DragEvent, DragItem, DragOperation, DragOutcome, DragResult,
+    DropDestination, DropLocation, Error, FileWriter, Image, ImageAnchor, LogicalPosition,
+    Modifiers, MonitorInfo, Options, PhysicalPosition, PhysicalSize, MOVED_EVENT_INTERVAL,
+};
 use gdkx11::{
     gdk,
-    glib::{ObjectExt, Propagation, SignalHandlerId},
+    glib::{
+        self, translate::ToGlibPtr, ControlFlow, ObjectExt, ObjectType, Propagation,
+        SignalHandlerId,
+    },
 };
 use gtk::{
     gdk_pixbuf,
     prelude::{
-        DeviceExt, DragContextExtManual, PixbufLoaderExt, SeatExt, WidgetExt, WidgetExtManual,
+        Cast, DeviceExt, DragContextExtManual, IsA, MonitorExt, PixbufLoaderExt, SeatExt,
+        WidgetExt, WidgetExtManual,
     },
+    TargetEntry, TargetFlags,
 };
+#[cfg(any(feature = "x11", feature = "wayland"))]
+use raw_window_handle::RawDisplayHandle;
+use raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawWindowHandle};
+
+use super::uri;
+
+mod drop_target;
+
+pub use drop_target::{register_drop_target, register_drop_target_on_widget, DropTarget};
 use std::{
+    cell::{Cell, RefCell},
+    ffi::{c_ulong, c_void},
+    path::PathBuf,
     rc::Rc,
     sync::{Arc, Mutex},
+    time::Instant,
 };
 
-pub fn start_drag<F: Fn(DragResult, CursorPosition) + Send + 'static>(
-    window: &gtk::ApplicationWindow,
+const HTML_TARGET: &str = "text/html";
+const URI_LIST_TARGET: &str = "text/uri-list";
+const NETSCAPE_URL_TARGET: &str = "_NETSCAPE_URL";
+const MOZ_URL_TARGET: &str = "text/x-moz-url";
+const XDS_TARGET: &str = "XdndDirectSave0";
+const XDS_PROPERTY_TYPE: &str = "text/plain";
+
+pub enum DragSession {
+    Gtk {
+        drag_context: gdk::DragContext,
+        state: Rc<SessionState>,
+    },
+    // the native X11 and Wayland drags block until they end, like on Windows
+    #[cfg(any(feature = "x11", feature = "wayland"))]
+    Native,
+}
+
+pub struct SessionState {
+    active: Cell<bool>,
+    cancelled: Cell<bool>,
+    promised_file: bool,
+    promised_file_path: RefCell<Option<PathBuf>>,
+    image_anchor: Option<ImageAnchor>,
+    // a target of this process only receives the drop after `drop-performed`,
+    // so the payload is kept until `drag-end`
+    payload: RefCell<Option<crate::local::Release>>,
+}
+
+impl DragSession {
+    pub fn cancel(&self) -> crate::Result<()> {
+        match self {
+            Self::Gtk {
+                drag_context,
+                state,
+            } => {
+                if state.active.get() {
+                    state.cancelled.set(true);
+                    drag_context.drag_cancel();
+                }
+            }
+            #[cfg(any(feature = "x11", feature = "wayland"))]
+            Self::Native => {}
+        }
+        Ok(())
+    }
+
+    pub fn is_active(&self) -> bool {
+        match self {
+            Self::Gtk { state, .. } => state.active.get(),
+            #[cfg(any(feature = "x11", feature = "wayland"))]
+            Self::Native => false,
+        }
+    }
+
+    pub fn set_image(&self, image: Image) -> crate::Result<()> {
+        match self {
+            Self::Gtk {
+                drag_context,
+                state,
+            } => {
+                if state.active.get() {
+                    let icon = image_to_pixbuf(&image).ok_or(Error::InvalidImage)?;
+                    set_icon(drag_context, &icon, state.image_anchor);
+                }
+            }
+            #[cfg(any(feature = "x11", feature = "wayland"))]
+            Self::Native => {}
+        }
+        Ok(())
+    }
+}
+
+pub fn start_drag<W: HasWindowHandle + HasDisplayHandle, F: Fn(DragOutcome) + Send + 'static>(
+    handle: &W,
     item: DragItem,
     image: Image,
     on_drop_callback: F,
     options: Options,
-) -> crate::Result<()> {
-    let handler_ids: Arc<Mutex<Vec<SignalHandlerId>>> = Arc::new(Mutex::new(vec![]));
+) -> crate::Result<crate::DragSession> {
+    let raw_handle = handle
+        .window_handle()
+        .map_err(|_| Error::UnsupportedWindowHandle)?
+        .as_raw();
+    if let Some(window) = find_gtk_window(raw_handle) {
+        return start_drag_from_widget(&window, item, image, on_drop_callback, options);
+    }
+
+    // windows created without GTK (e.g. winit) are driven through the native protocols
+    match raw_handle {
+        #[cfg(feature = "x11")]
+        RawWindowHandle::Xlib(window) => super::x11::start_drag(
+            raw_display_handle(handle)?,
+            window.window as u32,
+            item,
+            image,
+            on_drop_callback,
+            options,
+        ),
+        #[cfg(feature = "x11")]
+        RawWindowHandle::Xcb(window) => super::x11::start_drag(
+            raw_display_handle(handle)?,
+            window.window.get(),
+            item,
+            image,
+            on_drop_callback,
+            options,
+        ),
+        #[cfg(feature = "wayland")]
+        RawWindowHandle::Wayland(window) => super::wayland::start_drag(
+            wayland_display(handle)?,
+            window.surface.as_ptr(),
+            item,
+            image,
+            on_drop_callback,
+            options,
+        ),
+        _ => Err(Error::UnsupportedWindowHandle),
+    }
+}
+
+/// Keeps the pointer tracking of a Wayland window alive, see [`crate::prepare`].
+#[derive(Default)]
+pub struct PreparedWindow {
+    #[cfg(feature = "wayland")]
+    _tracker: Option<Arc<super::wayland::PointerTracker>>,
+}
+
+pub fn prepare<W: HasWindowHandle + HasDisplayHandle>(handle: &W) -> crate::Result<PreparedWindow> {
+    #[cfg(feature = "wayland")]
+    {
+        let raw_handle = handle
+            .window_handle()
+            .map_err(|_| Error::UnsupportedWindowHandle)?
+            .as_raw();
+        if matches!(raw_handle, RawWindowHandle::Wayland(_))
+            && find_gtk_window(raw_handle).is_none()
+        {
+            return Ok(PreparedWindow {
+                _tracker: Some(super::wayland::prepare(wayland_display(handle)?)?),
+            });
+        }
+    }
+    #[cfg(not(feature = "wayland"))]
+    let _ = handle;
+    Ok(PreparedWindow::default())
+}
+
+#[cfg(any(feature = "x11", feature = "wayland"))]
+fn raw_display_handle<W: HasDisplayHandle>(handle: &W) -> crate::Result<RawDisplayHandle> {
+    Ok(handle
+        .display_handle()
+        .map_err(|_| Error::UnsupportedWindowHandle)?
+        .as_raw())
+}
+
+#[cfg(feature = "wayland")]
+fn wayland_display<W: HasDisplayHandle>(handle: &W) -> crate::Result<*mut c_void> {
+    match raw_display_handle(handle)? {
+        RawDisplayHandle::Wayland(display) => Ok(display.display.as_ptr()),
+        _ => Err(Error::UnsupportedWindowHandle),
+    }
+}
 
-    window.drag_source_set(gdk::ModifierType::BUTTON1_MASK, &[], gdk::DragAction::COPY);
+pub fn start_drag_from_widget<W: IsA<gtk::Widget>, F: Fn(DragOutcome) + Send + 'static>(
+    widget: &W,
+    item: DragItem,
+    image: Image,
+    on_drop_callback: F,
+    mut options: Options,
+) -> crate::Result<crate::DragSession> {
+    let widget = widget.upcast_ref::<gtk::Widget>();
+    crate::validate::validate(&item, &image)?;
+    let payload = crate::local::attach(options.payload.take());
+    let icon = image_to_pixbuf(&image).ok_or(Error::InvalidImage)?;
+
+    let started_at = Instant::now();
+    let handler_ids: Arc<Mutex<Vec<SignalHandlerId>>> = Arc::new(Mutex::new(vec![]));
+    let actions = drag_actions(&options.allowed_operations);
+    let state = Rc::new(SessionState {
+        active: Cell::new(true),
+        cancelled: Cell::new(false),
+        promised_file: matches!(item, DragItem::PromisedFile { .. }),
+        promised_file_path: RefCell::new(None),
+        image_anchor: options.image_anchor,
+        payload: RefCell::new(payload),
+    });
+    let mut promised_file_name = None;
 
     match item {
         DragItem::Files(paths) => {
-            window.drag_source_add_uri_targets();
+            widget.drag_source_set(gdk::ModifierType::BUTTON1_MASK, &[], actions);
+            widget.drag_source_add_uri_targets();
             handler_ids
                 .lock()
                 .unwrap()
-                .push(window.connect_drag_data_get(move |_, _, data, _, _| {
-                    let uris: Vec<String> = paths
-                        .iter()
-                        .map(|path| format!("file://{}", path.display()))
-                        .collect();
-                    let uris: Vec<&str> = uris.iter().map(|s| s.as_str()).collect();
-                    data.set_uris(&uris);
+                .push(widget.connect_drag_data_get(move |_, _, data, _, _| {
+                    set_file_uris(data, &paths);
                 }));
         }
-        DragItem::Data { .. } => {
-            // Currently leaving it as is as we can utilize it as a dummy dragging feature
-            // on_drop_callback(DragResult::Cancel, get_cursor_position(window).unwrap());
-            // return Ok(());
+        DragItem::Data { provider, types } => {
+            let targets: Vec<TargetEntry> = types
+                .iter()
+                .enumerate()
+                .map(|(info, target)| TargetEntry::new(target, TargetFlags::empty(), info as u32))
+                .collect();
+            widget.drag_source_set(gdk::ModifierType::BUTTON1_MASK, &targets, actions);
+            handler_ids
+                .lock()
+                .unwrap()
+                .push(widget.connect_drag_data_get(move |_, _, data, _, _| {
+                    let target = data.target();
+                    if let Some(bytes) = provider(&target.name()) {
+                        data.set(&target, 8, &bytes);
+                    }
+                }));
+        }
+        DragItem::Mixed {
+            files,
+            data,
+            provider,
+            types,
+        } => {
+            let targets: Vec<TargetEntry> = data
+                .iter()
+                .map(|(target, _)| target)
+                .chain(types.iter())
+                .enumerate()
+                .map(|(info, target)| TargetEntry::new(target, TargetFlags::empty(), info as u32))
+                .collect();
+            widget.drag_source_set(gdk::ModifierType::BUTTON1_MASK, &targets, actions);
+            if !files.is_empty() {
+                widget.drag_source_add_uri_targets();
+            }
+            handler_ids
+                .lock()
+                .unwrap()
+                .push(
+                    widget.connect_drag_data_get(move |_, _, selection_data, _, _| {
+                        let target = selection_data.target();
+                        let name = target.name();
+                        if let Some((_, bytes)) = data.iter().find(|(t, _)| *t == name.as_str()) {
+                            selection_data.set(&target, 8, bytes);
+                        } else if types.iter().any(|t| *t == name.as_str()) {
+                            if let Some(bytes) =
+                                provider.as_ref().and_then(|provider| provider(&name))
+                            {
+                                selection_data.set(&target, 8, &bytes);
+                            }
+                        } else if !files.is_empty() {
+                            set_file_uris(selection_data, &files);
+                        }
+                    }),
+                );
+        }
+        DragItem::Text(text) => {
+            widget.drag_source_set(gdk::ModifierType::BUTTON1_MASK, &[], actions);
+            widget.drag_source_add_text_targets();
+            handler_ids
+                .lock()
+                .unwrap()
+                .push(widget.connect_drag_data_get(move |_, _, data, _, _| {
+                    data.set_text(&text);
+                }));
+        }
+        DragItem::Html {
+            html,
+            plain_fallback,
+        } => {
+            let targets = [TargetEntry::new(HTML_TARGET, TargetFlags::empty(), 0)];
+            widget.drag_source_set(gdk::ModifierType::BUTTON1_MASK, &targets, actions);
+            if plain_fallback.is_some() {
+                widget.drag_source_add_text_targets();
+            }
+            handler_ids
+                .lock()
+                .unwrap()
+                .push(widget.connect_drag_data_get(move |_, _, data, _, _| {
+                    let target = data.target();
+                    if target.name() == HTML_TARGET {
+                        data.set(&target, 8, html.as_bytes());
+                    } else if let Some(text) = &plain_fallback {
+                        data.set_text(text);
+                    }
+                }));
+        }
+        DragItem::Url { url, title } => {
+            let targets: Vec<TargetEntry> = [URI_LIST_TARGET, NETSCAPE_URL_TARGET, MOZ_URL_TARGET]
+                .iter()
+                .enumerate()
+                .map(|(info, target)| TargetEntry::new(target, TargetFlags::empty(), info as u32))
+                .collect();
+            widget.drag_source_set(gdk::ModifierType::BUTTON1_MASK, &targets, actions);
+            widget.drag_source_add_text_targets();
+            handler_ids
+                .lock()
+                .unwrap()
+                .push(widget.connect_drag_data_get(move |_, _, data, _, _| {
+                    let target = data.target();
+                    let url_with_title = uri::url_with_title(&url, title.as_deref());
+                    match target.name().as_str() {
+                        URI_LIST_TARGET => {
+                            data.set_uris(&[&url]);
+                        }
+                        NETSCAPE_URL_TARGET => {
+                            data.set(&target, 8, url_with_title.as_bytes());
+                        }
+                        MOZ_URL_TARGET => {
+                            let bytes: Vec<u8> = url_with_title
+                                .encode_utf16()
+                                .flat_map(u16::to_ne_bytes)
+                                .collect();
+                            data.set(&target, 8, &bytes);
+                        }
+                        _ => {
+                            data.set_text(&url);
+                        }
+                    }
+                }));
+        }
+        DragItem::PromisedFile { file_name, writer } => {
+            let targets = [TargetEntry::new(XDS_TARGET, TargetFlags::empty(), 0)];
+            widget.drag_source_set(gdk::ModifierType::BUTTON1_MASK, &targets, actions);
+            let state = state.clone();
+            handler_ids
+                .lock()
+                .unwrap()
+                .push(
+                    widget.connect_drag_data_get(move |_, drag_context, data, _, _| {
+                        // reply with "S" on success and "E" on error, as defined by the XDS protocol
+                        let status: &[u8] = match write_promised_file(drag_context, &writer) {
+                            Some(path) => {
+                                state.promised_file_path.replace(Some(path));
+                                b"S"
+                            }
+                            None => b"E",
+                        };
+                        data.set(&data.target(), 8, status);
+                    }),
+                );
+            promised_file_name = Some(file_name);
         }
     }
 
-    if let Some(target_list) = &window.drag_source_get_target_list() {
-        if let Some(drag_context) = window.drag_begin_with_coordinates(
-            target_list,
-            gdk::DragAction::COPY,
-            gdk::ffi::GDK_BUTTON1_MASK as i32,
-            None,
-            -1,
-            -1,
-        ) {
-            let callback = Rc::new(on_drop_callback);
-            on_drop_failed(callback.clone(), window, &handler_ids, &options);
-            on_drop_performed(callback.clone(), window, &handler_ids, &drag_context);
-
-            let icon_pixbuf: Option<gdk_pixbuf::Pixbuf> = match &image {
-                Image::Raw(data) => image_binary_to_pixbuf(data),
-                Image::File(path) => match std::fs::read(path) {
-                    Ok(bytes) => image_binary_to_pixbuf(&bytes),
-                    Err(_) => None,
-                },
-            };
-            if let Some(icon) = icon_pixbuf {
-                drag_context.drag_set_icon_pixbuf(&icon, 0, 0);
+    let drag_context = match &widget.drag_source_get_target_list() {
+        Some(target_list) => widget
+            .drag_begin_with_coordinates(
+                target_list,
+                actions,
+                gdk::ffi::GDK_BUTTON1_MASK as i32,
+                None,
+                -1,
+                -1,
+            )
+            .ok_or(Error::FailedToStartDrag),
+        None => Err(Error::EmptyTargetList),
+    };
+    let drag_context = match drag_context {
+        Ok(drag_context) => drag_context,
+        Err(e) => {
+            // the next drag from this widget would otherwise run the stale handlers
+            cleanup_signal_handlers(&handler_ids, widget);
+            return Err(e);
+        }
+    };
+
+    if let Some(file_name) = promised_file_name {
+        // the drop target reads the suggested name and writes back the destination URI
+        gdk::property_change(
+            &drag_context.source_window(),
+            &gdk::Atom::intern(XDS_TARGET),
+            &gdk::Atom::intern(XDS_PROPERTY_TYPE),
+            8,
+            gdk::PropMode::Replace,
+            gdk::ChangeData::UChars(file_name.as_bytes()),
+        );
+    }
+
+    let callback = Rc::new(on_drop_callback);
+    on_drop_failed(
+        callback.clone(),
+        widget,
+        &handler_ids,
+        &options,
+        &state,
+        started_at,
+    );
+    on_drop_performed(callback.clone(), widget, &drag_context, &state, started_at);
+    if let Some(on_move_completed) = options.on_move_completed.take() {
+        on_data_delete(on_move_completed, widget, &handler_ids);
+    }
+    on_drag_end(widget, &handler_ids, &state);
+    if let Some(on_event) = options.on_event.take() {
+        on_drag_events(on_event, widget, &drag_context, &state);
+    }
+
+    set_icon(&drag_context, &icon, options.image_anchor);
+
+    Ok(crate::DragSession {
+        inner: DragSession::Gtk {
+            drag_context,
+            state,
+        },
+    })
+}
+
+fn set_icon(
+    drag_context: &gdk::DragContext,
+    icon: &gdk_pixbuf::Pixbuf,
+    image_anchor: Option<ImageAnchor>,
+) {
+    let (hot_x, hot_y) = image_anchor.map_or((0., 0.), |anchor| {
+        anchor.offset(f64::from(icon.width()), f64::from(icon.height()))
+    });
+    drag_context.drag_set_icon_pixbuf(icon, hot_x.round() as i32, hot_y.round() as i32);
+}
+
+fn set_file_uris(data: &gtk::SelectionData, paths: &[PathBuf]) {
+    let uris: Vec<String> = paths.iter().map(|path| uri::file_uri(path)).collect();
+    let uris: Vec<&str> = uris.iter().map(|s| s.as_str()).collect();
+    data.set_uris(&uris);
+}
+
+// tao and tauri windows are GTK toplevels, so their handle points at the toplevel GDK window
+fn find_gtk_window(handle: RawWindowHandle) -> Option<gtk::Widget> {
+    // winit applications never initialize GTK, and listing the toplevels would panic
+    if !gtk::is_initialized_main_thread() {
+        return None;
+    }
+    gtk::Window::list_toplevels().into_iter().find(|toplevel| {
+        toplevel.window().is_some_and(|window| match handle {
+            RawWindowHandle::Xlib(handle) => {
+                x11_window_id(&window).is_some_and(|xid| xid == handle.window)
+            }
+            RawWindowHandle::Xcb(handle) => {
+                x11_window_id(&window).is_some_and(|xid| xid == handle.window.get() as c_ulong)
+            }
+            RawWindowHandle::Wayland(handle) => {
+                wayland_surface(&window) == Some(handle.surface.as_ptr())
             }
+            _ => false,
+        })
+    })
+}
+
+fn x11_window_id(window: &gdk::Window) -> Option<c_ulong> {
+    window
+        .downcast_ref::<gdkx11::X11Window>()
+        .map(|window| window.xid())
+}
+
+fn wayland_surface(window: &gdk::Window) -> Option<*mut c_void> {
+    if window.type_().name() != "GdkWaylandWindow" {
+        return None;
+    }
+    let surface =
+        unsafe { gdk_wayland_sys::gdk_wayland_window_get_wl_surface(window.as_ptr() as *mut _) };
+    (!surface.is_null()).then_some(surface)
+}
 
-            Ok(())
-        } else {
-            Err(crate::Error::FailedToStartDrag)
+pub(crate) fn image_to_pixbuf(image: &Image) -> Option<gdk_pixbuf::Pixbuf> {
+    match image {
+        Image::Raw(data) => image_binary_to_pixbuf(data),
+        Image::Rgba {
+            width,
+            height,
+            pixels,
+        } => {
+            let width = i32::try_from(*width).ok()?;
+            let height = i32::try_from(*height).ok()?;
+            Some(gdk_pixbuf::Pixbuf::from_bytes(
+                &glib::Bytes::from(pixels.as_slice()),
+                gdk_pixbuf::Colorspace::Rgb,
+                true,
+                8,
+                width,
+                height,
+                width.checked_mul(4)?,
+            ))
         }
-    } else {
-        Err(crate::Error::EmptyTargetList)
+        Image::File(path) => match std::fs::read(path) {
+            Ok(bytes) => image_binary_to_pixbuf(&bytes),
+            Err(_) => None,
+        },
     }
 }
 
@@ -94,74 +536,467 @@ fn image_binary_to_pixbuf(data: &[u8]) -> Option<gdk_pixbuf::Pixbuf> {
         .ok()
 }
 
-fn clear_signal_handlers(window: &gtk::ApplicationWindow, handler_ids: &mut Vec<SignalHandlerId>) {
+/// Converts the pixbuf to the premultiplied ARGB pixels X11 and Wayland expect, in the given byte order.
+#[cfg(any(feature = "x11", feature = "wayland"))]
+pub(crate) fn premultiplied_argb(pixbuf: &gdk_pixbuf::Pixbuf, lsb_first: bool) -> Vec<u8> {
+    let (width, height) = (pixbuf.width() as usize, pixbuf.height() as usize);
+    let rowstride = pixbuf.rowstride() as usize;
+    let channels = pixbuf.n_channels() as usize;
+    let bytes = pixbuf.read_pixel_bytes();
+
+    let mut pixels = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let offset = y * rowstride + x * channels;
+            let pixel = &bytes[offset..offset + channels];
+            let alpha = if pixbuf.has_alpha() { pixel[3] } else { 255 };
+            let premultiply = |channel: u8| u32::from(channel) * u32::from(alpha) / 255;
+            let argb = (u32::from(alpha) << 24)
+                | (premultiply(pixel[0]) << 16)
+                | (premultiply(pixel[1]) << 8)
+                | premultiply(pixel[2]);
+            pixels.extend(if lsb_first {
+                argb.to_le_bytes()
+            } else {
+                argb.to_be_bytes()
+            });
+        }
+    }
+    pixels
+}
+
+fn clear_signal_handlers(widget: &gtk::Widget, handler_ids: &mut Vec<SignalHandlerId>) {
     for handler_id in handler_ids.drain(..) {
-        window.disconnect(handler_id);
+        widget.disconnect(handler_id);
     }
 }
 
-fn on_drop_failed<F: Fn(DragResult, CursorPosition) + Send + 'static>(
+fn on_drop_failed<F: Fn(DragOutcome) + Send + 'static>(
     callback: Rc<F>,
-    window: &gtk::ApplicationWindow,
+    widget: &gtk::Widget,
     handler_ids: &Arc<Mutex<Vec<SignalHandlerId>>>,
     options: &Options,
+    state: &Rc<SessionState>,
+    started_at: Instant,
 ) {
-    let window_clone = window.clone();
-    let handler_ids_clone = handler_ids.clone();
+    let widget_clone = widget.clone();
+    let state = state.clone();
 
     let skip_animatation_on_cancel_or_failure = options.skip_animatation_on_cancel_or_failure;
 
-    handler_ids
-        .lock()
-        .unwrap()
-        .push(window.connect_drag_failed(move |_, _, _drag_result| {
-            callback(
-                DragResult::Cancel,
-                get_cursor_position(&window_clone).unwrap(),
-            );
+    handler_ids.lock().unwrap().push(widget.connect_drag_failed(
+        move |_, drag_context, drag_result| {
+            let reason = if state.cancelled.get() {
+                CancelReason::Programmatic
+            } else {
+                cancel_reason(drag_result)
+            };
+            callback(get_drag_outcome(
+                &widget_clone,
+                drag_context,
+                DragResult::Cancel(reason),
+                &state,
+                started_at,
+            ));
 
-            cleanup_signal_handlers(&handler_ids_clone, &window_clone);
             if skip_animatation_on_cancel_or_failure {
                 Propagation::Stop
             } else {
                 Propagation::Proceed
             }
-        }));
+        },
+    ));
 }
 
-fn cleanup_signal_handlers(
-    handler_ids: &Arc<Mutex<Vec<SignalHandlerId>>>,
-    window: &gtk::ApplicationWindow,
-) {
+fn cleanup_signal_handlers(handler_ids: &Arc<Mutex<Vec<SignalHandlerId>>>, widget: &gtk::Widget) {
     let handler_ids = &mut handler_ids.lock().unwrap();
-    clear_signal_handlers(window, handler_ids);
-    window.drag_source_unset();
+    clear_signal_handlers(widget, handler_ids);
+    widget.drag_source_unset();
 }
 
-fn on_drop_performed<F: Fn(DragResult, CursorPosition) + Send + 'static>(
+fn on_drop_performed<F: Fn(DragOutcome) + Send + 'static>(
     callback: Rc<F>,
-    window: &gtk::ApplicationWindow,
+    widget: &gtk::Widget,
+    drag_context: &gdk::DragContext,
+    state: &Rc<SessionState>,
+    started_at: Instant,
+) {
+    let widget = widget.clone();
+    let state_ = state.clone();
+    let report = move |drag_context: &gdk::DragContext| {
+        callback(get_drag_outcome(
+            &widget,
+            drag_context,
+            DragResult::Dropped(drag_operation(drag_context.selected_action())),
+            &state_,
+            started_at,
+        ));
+    };
+
+    if state.promised_file {
+        // the promised file is only written after the drop, once the target requests it
+        drag_context.connect_dnd_finished(report);
+    } else {
+        drag_context.connect_drop_performed(move |drag_context, _| report(drag_context));
+    }
+}
+
+fn on_data_delete(
+    on_move_completed: Box<dyn Fn() + Send>,
+    widget: &gtk::Widget,
     handler_ids: &Arc<Mutex<Vec<SignalHandlerId>>>,
+) {
+    handler_ids
+        .lock()
+        .unwrap()
+        .push(widget.connect_drag_data_delete(move |_, _| {
+            on_move_completed();
+        }));
+}
+
+// the target might still request data after the drop is performed,
+// so the handlers (including `drag-data-get`) are only removed when the drag ends
+fn on_drag_end(
+    widget: &gtk::Widget,
+    handler_ids: &Arc<Mutex<Vec<SignalHandlerId>>>,
+    state: &Rc<SessionState>,
+) {
+    let widget_clone = widget.clone();
+    let handler_ids_clone = handler_ids.clone();
+    let state = state.clone();
+
+    handler_ids
+        .lock()
+        .unwrap()
+        .push(widget.connect_drag_end(move |_, drag_context| {
+            state.active.set(false);
+            state.payload.take();
+            if state.promised_file {
+                gdk::property_delete(
+                    &drag_context.source_window(),
+                    &gdk::Atom::intern(XDS_TARGET),
+                );
+            }
+            cleanup_signal_handlers(&handler_ids_clone, &widget_clone);
+        }));
+}
+
+fn on_drag_events(
+    on_event: Box<dyn Fn(DragEvent) + Send>,
+    widget: &gtk::Widget,
     drag_context: &gdk::DragContext,
+    state: &Rc<SessionState>,
 ) {
-    let window = window.clone();
-    let handler_ids = handler_ids.clone();
+    let on_event = Rc::new(on_event);
+    on_event(DragEvent::Started);
+
+    let on_action_changed = on_event.clone();
+    drag_context.connect_action_changed(move |_, action| {
+        on_action_changed(DragEvent::ActionChanged(
+            (!action.is_empty()).then(|| drag_operation(action)),
+        ));
+    });
+
+    // GDK does not report the pointer motion of the drag source, so it is polled instead
+    let widget = widget.clone();
+    let drag_context = drag_context.clone();
+    let state = state.clone();
+    let mut last_position = None;
+    let mut last_dest_window = None;
+    glib::timeout_add_local(MOVED_EVENT_INTERVAL, move || {
+        if !state.active.get() {
+            return ControlFlow::Break;
+        }
+
+        if let Some(position) = get_cursor_position(&widget) {
+            if last_position != Some((position.x, position.y)) {
+                last_position = Some((position.x, position.y));
+                on_event(DragEvent::Moved(position));
+            }
+        }
+
+        let dest_window = dest_window(&drag_context);
+        if dest_window != last_dest_window {
+            if last_dest_window.is_some() {
+                on_event(DragEvent::Left);
+            }
+            if dest_window.is_some() {
+                on_event(DragEvent::Entered);
+            }
+            last_dest_window = dest_window;
+        }
 
-    drag_context.connect_drop_performed(move |_, _| {
-        cleanup_signal_handlers(&handler_ids, &window);
-        callback(DragResult::Dropped, get_cursor_position(&window).unwrap());
+        ControlFlow::Continue
     });
 }
 
-fn get_cursor_position(window: &gtk::ApplicationWindow) -> Result<CursorPosition, Error> {
-    if let Some(cursor) = window
-        .display()
-        .default_seat()
-        .and_then(|seat| seat.pointer())
-    {
-        let (_, x, y) = cursor.position();
-        Ok(CursorPosition { x, y })
+// `gdk::DragContext::dest_window` does not handle the NULL returned when there is no destination
+fn dest_window(drag_context: &gdk::DragContext) -> Option<gdk::Window> {
+    unsafe {
+        let window = gdk::ffi::gdk_drag_context_get_dest_window(drag_context.to_glib_none().0);
+        glib::translate::from_glib_none(window)
+    }
+}
+
+fn drag_actions(operations: &[DragOperation]) -> gdk::DragAction {
+    operations
+        .iter()
+        .fold(gdk::DragAction::empty(), |actions, operation| {
+            actions
+                | match operation {
+                    DragOperation::Copy => gdk::DragAction::COPY,
+                    DragOperation::Move => gdk::DragAction::MOVE,
+                    DragOperation::Link => gdk::DragAction::LINK,
+                    DragOperation::Ask => gdk::DragAction::ASK,
+                }
+        })
+}
+
+fn drag_operation(action: gdk::DragAction) -> DragOperation {
+    if action.contains(gdk::DragAction::MOVE) {
+        DragOperation::Move
+    } else if action.contains(gdk::DragAction::LINK) {
+        DragOperation::Link
+    } else if action.contains(gdk::DragAction::ASK) {
+        DragOperation::Ask
     } else {
-        Err(Error::FailedToGetCursorPosition)
+        DragOperation::Copy
+    }
+}
+
+fn cancel_reason(drag_result: gtk::DragResult) -> CancelReason {
+    match drag_result {
+        gtk::DragResult::NoTarget => CancelReason::NoTarget,
+        gtk::DragResult::UserCancelled => CancelReason::UserCancelled,
+        gtk::DragResult::TimeoutExpired => CancelReason::TimeoutExpired,
+        gtk::DragResult::GrabBroken => CancelReason::GrabBroken,
+        _ => CancelReason::Error,
+    }
+}
+
+fn get_drag_outcome(
+    widget: &gtk::Widget,
+    drag_context: &gdk::DragContext,
+    result: DragResult,
+    state: &SessionState,
+    started_at: Instant,
+) -> DragOutcome {
+    DragOutcome {
+        result,
+        cursor_position: get_cursor_position(widget),
+        modifiers: get_modifiers(widget),
+        duration: started_at.elapsed(),
+        promised_file_path: state.promised_file_path.take(),
+        destination: drop_destination(drag_context),
+        drop_location: get_drop_location(widget),
+    }
+}
+
+// GDK maps the destination window back to our own window when it belongs to this process
+#[allow(clippy::unnecessary_cast)] // X11 window ids are `c_ulong`, which is `u32` on 32-bit targets
+fn drop_destination(drag_context: &gdk::DragContext) -> Option<DropDestination> {
+    let dest_window = dest_window(drag_context);
+    if let Some(toplevel) = dest_window.as_ref().map(|window| window.toplevel()) {
+        let own = gtk::Window::list_toplevels()
+            .into_iter()
+            .any(|window| window.window().as_ref() == Some(&toplevel));
+        if own {
+            return Some(DropDestination::OwnWindow {
+                window_id: x11_window_id(&toplevel).map(|xid| xid as u64),
+            });
+        }
+    }
+
+    // on X11 the drag context only knows about windows accepting drops
+    let display = drag_context.source_window().display();
+    let x11 = display.is::<gdkx11::X11Display>();
+    #[cfg(feature = "x11")]
+    if x11 {
+        let display = display.downcast_ref::<gdkx11::X11Display>()?;
+        // Safety: GDK keeps the display open and uses it on this thread only
+        return unsafe {
+            super::x11::destination_under_pointer(
+                gdkx11::ffi::gdk_x11_display_get_xdisplay(display.as_ptr()).cast(),
+            )
+        };
+    }
+    match dest_window {
+        Some(window) => Some(DropDestination::Foreign {
+            window_id: x11_window_id(&window).map(|xid| xid as u64),
+            app_class: None,
+            pid: None,
+        }),
+        None => x11.then_some(DropDestination::Desktop),
+    }
+}
+
+// the drop target stores the destination URI in the property holding the suggested file name
+fn write_promised_file(drag_context: &gdk::DragContext, writer: &FileWriter) -> Option<PathBuf> {
+    let (_, _, uri) = gdk::property_get(
+        &drag_context.source_window(),
+        &gdk::Atom::intern(XDS_TARGET),
+        &gdk::Atom::intern(XDS_PROPERTY_TYPE),
+        0,
+        1024,
+        0,
+    )?;
+    let uri = String::from_utf8(uri).ok()?;
+    let (path, _) = glib::filename_from_uri(&uri).ok()?;
+    writer(&path).ok()?;
+    Some(path)
+}
+
+fn get_modifiers(widget: &gtk::Widget) -> Modifiers {
+    let state = gdk::Keymap::for_display(&widget.display())
+        .map(|keymap| gdk::ModifierType::from_bits_truncate(keymap.modifier_state()))
+        .unwrap_or_else(gdk::ModifierType::empty);
+    Modifiers {
+        shift: state.contains(gdk::ModifierType::SHIFT_MASK),
+        control: state.contains(gdk::ModifierType::CONTROL_MASK),
+        alt: state.contains(gdk::ModifierType::MOD1_MASK),
+        meta: state.intersects(gdk::ModifierType::SUPER_MASK | gdk::ModifierType::META_MASK),
+    }
+}
+
+fn get_cursor_position(widget: &gtk::Widget) -> Option<CursorPosition> {
+    // a headless seat has no pointer device to query
+    let pointer = widget.display().default_seat()?.pointer()?;
+    let (_, x, y) = pointer.position();
+    Some(CursorPosition { x, y })
+}
+
+// GDK positions are in logical pixels, the global position is only known on X11
+fn get_drop_location(widget: &gtk::Widget) -> Option<DropLocation> {
+    let display = widget.display();
+    if !display.is::<gdkx11::X11Display>() {
+        return None;
+    }
+    let pointer = display.default_seat()?.pointer()?;
+    let (_, x, y) = pointer.position_double();
+    let monitor = display.monitor_at_point(x as i32, y as i32);
+    let window_position = widget
+        .toplevel()
+        .and_then(|toplevel| toplevel.window())
+        .map(|window| {
+            let (_, x, y, _) = window.device_position_double(&pointer);
+            LogicalPosition { x, y }
+        });
+    Some(DropLocation::from_logical(
+        LogicalPosition { x, y },
+        window_position,
+        monitor.as_ref().map(monitor_info),
+    ))
+}
+
+fn monitor_info(monitor: &gdk::Monitor) -> MonitorInfo {
+    let geometry = monitor.geometry();
+    let scale_factor = monitor.scale_factor();
+    MonitorInfo {
+        name: monitor.model().map(|model| model.to_string()),
+        position: PhysicalPosition {
+            x: geometry.x() * scale_factor,
+            y: geometry.y() * scale_factor,
+        },
+        size: PhysicalSize {
+            width: (geometry.width() * scale_factor) as u32,
+            height: (geometry.height() * scale_factor) as u32,
+        },
+        scale_factor: f64::from(scale_factor),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DropEvent, DropResponse};
+    use gtk::prelude::{GtkWindowExt, WidgetExt};
+    use std::time::Duration;
+
+    fn popup(x: i32, y: i32) -> gtk::Window {
+        let window = gtk::Window::new(gtk::WindowType::Popup);
+        window.set_default_size(100, 100);
+        window.move_(x, y);
+        window.show_all();
+        window
+    }
+
+    fn iterate_until(condition: impl Fn() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !condition() && Instant::now() < deadline {
+            gtk::main_iteration_do(false);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    // run under Xvfb with `cargo test -- --ignored`
+    #[test]
+    #[ignore = "requires a display"]
+    fn local_payload_reaches_a_drop_target_of_this_process() {
+        gtk::init().unwrap();
+        let source = popup(0, 0);
+        let target = popup(200, 0);
+
+        let received = Rc::new(RefCell::new(None));
+        let received_ = received.clone();
+        let _drop_target = register_drop_target_on_widget(&target, move |event| {
+            if let DropEvent::Dropped { .. } = event {
+                *received_.borrow_mut() = crate::take_local_payload()
+                    .and_then(|payload| payload.downcast::<u32>().ok())
+                    .map(|payload| *payload);
+            }
+            DropResponse::Accept(DragOperation::Copy)
+        })
+        .unwrap();
+        iterate_until(|| target.window().is_some_and(|window| window.is_viewable()));
+
+        let session = start_drag_from_widget(
+            &source,
+            DragItem::Text("drag".into()),
+            Image::Rgba {
+                width: 1,
+                height: 1,
+                pixels: vec![0; 4],
+            },
+            |_| {},
+            Options {
+                payload: Some(Box::new(42u32)),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        #[allow(irrefutable_let_patterns)]
+        let DragSession::Gtk { drag_context, .. } = &session.inner
+        else {
+            unreachable!("a GTK widget starts a GTK drag")
+        };
+
+        // there is no pointer to move under Xvfb, so the drag is driven the way GDK does it
+        let time = gdk::ffi::GDK_CURRENT_TIME as u32;
+        let target_window = target.window().unwrap();
+        let (_, x, y) = target_window.origin();
+        let (dest_window, protocol) = drag_context.drag_find_window_for_screen(
+            &drag_context.source_window(),
+            &target_window.screen(),
+            x + 50,
+            y + 50,
+        );
+        drag_context.drag_motion(
+            &dest_window.expect("the target window is under the drop position"),
+            protocol,
+            x + 50,
+            y + 50,
+            gdk::DragAction::COPY,
+            gdk::DragAction::COPY,
+            time,
+        );
+        iterate_until(|| !drag_context.selected_action().is_empty());
+
+        // GDK reports the drop as performed before the target receives it
+        drag_context.emit_by_name::<()>("drop-performed", &[&(time as i32)]);
+        drag_context.drop(time);
+        iterate_until(|| !session.is_active());
+
+        assert_eq!(*received.borrow(), Some(42));
+        assert!(!crate::local::is_available());
     }
 }
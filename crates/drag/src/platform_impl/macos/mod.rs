@@ -2,24 +2,53 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-License-Identifier: MIT
 
-use std::ffi::{c_char, c_void};
+use std::{
+    ffi::{c_char, c_void},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
 
 use cocoa::{
     appkit::{NSAlignmentOptions, NSApp, NSEvent, NSEventModifierFlags, NSEventType, NSImage},
     base::{id, nil},
-    foundation::{NSArray, NSData, NSPoint, NSRect, NSSize, NSUInteger},
+    foundation::{NSArray, NSData, NSInteger, NSPoint, NSRect, NSSize, NSUInteger},
 };
 use core_graphics::display::CGDisplay;
 use objc::{
     declare::ClassDecl,
     runtime::{Class, Object, Protocol, Sel, BOOL, NO, YES},
 };
-use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+use raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawWindowHandle};
 
-use crate::{CursorPosition, DragItem, DragResult, Image, Options};
+use crate::{
+    CancelReason, CursorPosition, DataProvider, DragEvent, DragItem, DragOperation, DragOutcome,
+    DragResult, DropDestination, DropEvent, DropLocation, DropResponse, Image, ImageAnchor,
+    LogicalPosition, Modifiers, MonitorInfo, Options, PhysicalPosition, PhysicalSize,
+};
 
 const UTF8_ENCODING: usize = 4;
 
+const NS_PASTEBOARD_TYPE_STRING: &str = "public.utf8-plain-text";
+const NS_PASTEBOARD_TYPE_HTML: &str = "public.html";
+const NS_PASTEBOARD_TYPE_URL: &str = "public.url";
+const NS_PASTEBOARD_TYPE_URL_NAME: &str = "public.url-name";
+
+const NS_DRAG_OPERATION_COPY: NSUInteger = 1;
+const NS_DRAG_OPERATION_LINK: NSUInteger = 2;
+const NS_DRAG_OPERATION_MOVE: NSUInteger = 16;
+const NS_DRAG_OPERATION_DELETE: NSUInteger = 32;
+
+const CG_WINDOW_LIST_OPTION_INCLUDING_WINDOW: u32 = 1 << 3;
+
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    // returns a `CFArrayRef` of `CFDictionaryRef`, toll-free bridged to `NSArray` and `NSDictionary`
+    fn CGWindowListCopyWindowInfo(option: u32, relative_to_window: u32) -> id;
+}
+
 struct NSString(id);
 
 impl NSString {
@@ -50,13 +79,63 @@ impl NSString {
     }
 }
 
-pub fn start_drag<W: HasWindowHandle, F: Fn(DragResult, CursorPosition) + Send + 'static>(
+type OnDropCallback =
+    Box<dyn Fn(DragResult, CursorPosition, Modifiers, DropDestination, DropLocation) + Send>;
+type OnEventCallback = Box<dyn Fn(DragEvent) + Send>;
+
+pub struct DragSession {
+    active: Arc<AtomicBool>,
+}
+
+impl DragSession {
+    pub fn cancel(&self) -> crate::Result<()> {
+        if self.is_active() {
+            Err(crate::Error::UnsupportedOperation)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    pub fn set_image(&self, _image: Image) -> crate::Result<()> {
+        if self.is_active() {
+            Err(crate::Error::UnsupportedOperation)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+pub struct DropTarget;
+
+impl DropTarget {
+    pub fn unregister(self) -> crate::Result<()> {
+        Ok(())
+    }
+}
+
+pub fn register_drop_target<W: HasWindowHandle, F: Fn(DropEvent) -> DropResponse + 'static>(
+    _handle: &W,
+    _handler: F,
+) -> crate::Result<crate::DropTarget> {
+    Err(crate::Error::UnsupportedOperation)
+}
+
+pub fn start_drag<W: HasWindowHandle + HasDisplayHandle, F: Fn(DragOutcome) + Send + 'static>(
     handle: &W,
     item: DragItem,
     image: Image,
     on_drop_callback: F,
-    options: Options,
-) -> crate::Result<()> {
+    mut options: Options,
+) -> crate::Result<crate::DragSession> {
+    crate::validate::validate(&item, &image)?;
+    let payload = crate::local::attach(options.payload.take());
+
+    let started_at = Instant::now();
+    let active = Arc::new(AtomicBool::new(true));
     if let Ok(RawWindowHandle::AppKit(w)) = handle.window_handle().map(|h| h.as_raw()) {
         unsafe {
             let window: id = msg_send![w.ns_view.as_ptr() as id, window];
@@ -69,9 +148,6 @@ pub fn start_drag<W: HasWindowHandle, F: Fn(DragResult, CursorPosition) + Send +
             let img: id = msg_send![class!(NSImage), alloc];
             let img: id = match image {
                 Image::File(path) => {
-                    if !path.exists() {
-                        return Err(crate::Error::ImageNotFound);
-                    }
                     NSImage::initByReferencingFile_(img, NSString::new(&path.to_string_lossy()).0)
                 }
                 Image::Raw(bytes) => {
@@ -82,12 +158,61 @@ pub fn start_drag<W: HasWindowHandle, F: Fn(DragResult, CursorPosition) + Send +
                     );
                     NSImage::initWithData_(NSImage::alloc(nil), data)
                 }
+                Image::Rgba {
+                    width,
+                    height,
+                    pixels,
+                } => {
+                    let rep: id = msg_send![class!(NSBitmapImageRep), alloc];
+                    // NSAlphaNonpremultipliedBitmapFormat, the planes are allocated by the rep
+                    let rep: id = msg_send![rep,
+                        initWithBitmapDataPlanes: std::ptr::null_mut::<*mut u8>()
+                        pixelsWide: width as NSInteger
+                        pixelsHigh: height as NSInteger
+                        bitsPerSample: 8 as NSInteger
+                        samplesPerPixel: 4 as NSInteger
+                        hasAlpha: YES
+                        isPlanar: NO
+                        colorSpaceName: NSString::new("NSDeviceRGBColorSpace").0
+                        bitmapFormat: (1 << 1) as NSUInteger
+                        bytesPerRow: (width * 4) as NSInteger
+                        bitsPerPixel: 32 as NSInteger];
+                    if rep == nil {
+                        nil
+                    } else {
+                        let data: *mut u8 = msg_send![rep, bitmapData];
+                        std::ptr::copy_nonoverlapping(pixels.as_ptr(), data, pixels.len());
+                        let img: id =
+                            msg_send![img, initWithSize: NSSize::new(width as f64, height as f64)];
+                        let () = msg_send![img, addRepresentation: rep];
+                        let () = msg_send![rep, release];
+                        img
+                    }
+                }
             };
+            let is_valid: BOOL = if img == nil {
+                NO
+            } else {
+                msg_send![img, isValid]
+            };
+            if is_valid == NO {
+                return Err(crate::Error::InvalidImage);
+            }
             let image_size: NSSize = img.size();
+            let (hot_x, hot_y) = options
+                .image_anchor
+                .unwrap_or(ImageAnchor::CENTER)
+                .offset(image_size.width, image_size.height);
+            // the anchor is measured from the top-left corner, while the view may have its origin at the bottom
+            let flipped: BOOL = msg_send![ns_view, isFlipped];
             let image_rect = NSRect::new(
                 NSPoint::new(
-                    current_position.x - image_size.width / 2.,
-                    current_position.y - image_size.height / 2.,
+                    current_position.x - hot_x,
+                    if flipped == YES {
+                        current_position.y - hot_y
+                    } else {
+                        current_position.y - image_size.height + hot_y
+                    },
                 ),
                 image_size,
             );
@@ -107,80 +232,79 @@ pub fn start_drag<W: HasWindowHandle, F: Fn(DragResult, CursorPosition) + Send +
                     }
                 }
                 DragItem::Data { provider, types } => {
-                    let cls = ClassDecl::new("DragRsDataProvider", class!(NSObject));
-                    let cls = match cls {
-                        Some(mut cls) => {
-                            cls.add_ivar::<*mut c_void>("provider_ptr");
-                            cls.add_protocol(
-                                Protocol::get("NSPasteboardItemDataProvider").unwrap(),
-                            );
-                            cls.add_method(
-                                sel!(pasteboard:item:provideDataForType:),
-                                provide_data as extern "C" fn(&Object, Sel, id, id, id),
-                            );
-                            cls.add_method(
-                                sel!(pasteboardFinishedWithDataProvider:),
-                                pasteboard_finished as extern "C" fn(&Object, Sel, id),
-                            );
+                    let item = pasteboard_item(Vec::new(), Some(provider), types);
 
-                            extern "C" fn pasteboard_finished(
-                                this: &Object,
-                                _: Sel,
-                                _pasteboard: id,
-                            ) {
-                                unsafe {
-                                    let provider = this.get_ivar::<*mut c_void>("provider_ptr");
-                                    drop(Box::from_raw(
-                                        *provider as *mut Box<dyn Fn(&str) -> Option<Vec<u8>>>,
-                                    ));
-                                }
-                            }
+                    let drag_item: id = msg_send![class!(NSDraggingItem), alloc];
+                    let item: id = msg_send![drag_item, initWithPasteboardWriter: item];
 
-                            extern "C" fn provide_data(
-                                this: &Object,
-                                _: Sel,
-                                _pasteboard: id,
-                                item: id,
-                                data_type: id,
-                            ) {
-                                unsafe {
-                                    let provider = this.get_ivar::<*mut c_void>("provider_ptr");
-
-                                    let provider =
-                                        &*(*provider as *mut Box<dyn Fn(&str) -> Option<Vec<u8>>>);
-
-                                    if let Some(data) = provider(NSString(data_type).to_str()) {
-                                        let bytes = data.as_ptr() as *mut c_void;
-                                        let length = data.len();
-                                        let data: id = msg_send![class!(NSData), alloc];
-                                        let data: id = msg_send![data, initWithBytesNoCopy:bytes length:length freeWhenDone: if length == 0 { NO } else { YES }];
-
-                                        let _: () =
-                                            msg_send![item, setData: data forType: data_type];
-                                    }
-                                }
-                            }
+                    let _: () = msg_send![item, setDraggingFrame: image_rect contents: img];
 
-                            cls.register()
-                        }
-                        None => Class::get("DragRsDataProvider")
-                            .expect("Failed to get the class definition"),
-                    };
+                    let _: () = msg_send![dragging_items, addObject: item];
+                }
+                DragItem::Mixed {
+                    files,
+                    data,
+                    provider,
+                    types,
+                } => {
+                    for path in files {
+                        let nsurl: id = msg_send![class!(NSURL), fileURLWithPath: NSString::new(&path.display().to_string()) isDirectory: false];
+                        let drag_item: id = msg_send![class!(NSDraggingItem), alloc];
+                        let item: id = msg_send![drag_item, initWithPasteboardWriter: nsurl];
+
+                        let _: () = msg_send![item, setDraggingFrame: image_rect contents: img];
+
+                        let _: () = msg_send![dragging_items, addObject: item];
+                    }
+
+                    if !data.is_empty() || !types.is_empty() {
+                        let item = pasteboard_item(data, provider, types);
+
+                        let drag_item: id = msg_send![class!(NSDraggingItem), alloc];
+                        let item: id = msg_send![drag_item, initWithPasteboardWriter: item];
+
+                        let _: () = msg_send![item, setDraggingFrame: image_rect contents: img];
+
+                        let _: () = msg_send![dragging_items, addObject: item];
+                    }
+                }
+                DragItem::Text(text) => {
+                    let data = vec![(NS_PASTEBOARD_TYPE_STRING.to_string(), text.into_bytes())];
+                    let item = pasteboard_item(data, None, Vec::new());
+
+                    let drag_item: id = msg_send![class!(NSDraggingItem), alloc];
+                    let item: id = msg_send![drag_item, initWithPasteboardWriter: item];
 
-                    let data_provider: id = msg_send![cls, alloc];
-                    let data_provider: id = msg_send![data_provider, init];
+                    let _: () = msg_send![item, setDraggingFrame: image_rect contents: img];
 
-                    let provider_ptr = Box::into_raw(Box::new(provider));
-                    (*data_provider)
-                        .set_ivar("provider_ptr", provider_ptr as *mut _ as *mut c_void);
+                    let _: () = msg_send![dragging_items, addObject: item];
+                }
+                DragItem::Html {
+                    html,
+                    plain_fallback,
+                } => {
+                    let mut data = vec![(NS_PASTEBOARD_TYPE_HTML.to_string(), html.into_bytes())];
+                    if let Some(text) = plain_fallback {
+                        data.push((NS_PASTEBOARD_TYPE_STRING.to_string(), text.into_bytes()));
+                    }
+                    let item = pasteboard_item(data, None, Vec::new());
 
-                    let item: id = msg_send![class!(NSPasteboardItem), alloc];
-                    let item: id = msg_send![item, init];
-                    let types = types
-                        .into_iter()
-                        .map(|t| NSString::new(&t).0)
-                        .collect::<Vec<id>>();
-                    let _: () = msg_send![item, setDataProvider: data_provider forTypes: NSArray::arrayWithObjects(nil, &types)];
+                    let drag_item: id = msg_send![class!(NSDraggingItem), alloc];
+                    let item: id = msg_send![drag_item, initWithPasteboardWriter: item];
+
+                    let _: () = msg_send![item, setDraggingFrame: image_rect contents: img];
+
+                    let _: () = msg_send![dragging_items, addObject: item];
+                }
+                DragItem::Url { url, title } => {
+                    let mut data = vec![
+                        (NS_PASTEBOARD_TYPE_URL.to_string(), url.clone().into_bytes()),
+                        (NS_PASTEBOARD_TYPE_STRING.to_string(), url.into_bytes()),
+                    ];
+                    if let Some(title) = title {
+                        data.push((NS_PASTEBOARD_TYPE_URL_NAME.to_string(), title.into_bytes()));
+                    }
+                    let item = pasteboard_item(data, None, Vec::new());
 
                     let drag_item: id = msg_send![class!(NSDraggingItem), alloc];
                     let item: id = msg_send![drag_item, initWithPasteboardWriter: item];
@@ -189,6 +313,8 @@ pub fn start_drag<W: HasWindowHandle, F: Fn(DragResult, CursorPosition) + Send +
 
                     let _: () = msg_send![dragging_items, addObject: item];
                 }
+                // rejected by `validate` before any native call
+                DragItem::PromisedFile { .. } => {}
             }
 
             let drag_event: id = msg_send![class!(NSEvent), alloc];
@@ -210,12 +336,23 @@ pub fn start_drag<W: HasWindowHandle, F: Fn(DragResult, CursorPosition) + Send +
             let cls = match cls {
                 Some(mut cls) => {
                     cls.add_ivar::<*mut c_void>("on_drop_ptr");
+                    cls.add_ivar::<*mut c_void>("on_event_ptr");
                     cls.add_ivar::<BOOL>("animate_on_cancel_or_failure");
+                    cls.add_ivar::<NSUInteger>("operation_mask");
+                    cls.add_ivar::<id>("window");
                     cls.add_method(
                         sel!(draggingSession:sourceOperationMaskForDraggingContext:),
                         dragging_session
                             as extern "C" fn(&Object, Sel, id, NSUInteger) -> NSUInteger,
                     );
+                    cls.add_method(
+                        sel!(draggingSession:willBeginAtPoint:),
+                        dragging_session_begin as extern "C" fn(&Object, Sel, id, NSPoint),
+                    );
+                    cls.add_method(
+                        sel!(draggingSession:movedToPoint:),
+                        dragging_session_moved as extern "C" fn(&Object, Sel, id, NSPoint),
+                    );
                     cls.add_method(
                         sel!(draggingSession:endedAtPoint:operation:),
                         dragging_session_end
@@ -226,19 +363,47 @@ pub fn start_drag<W: HasWindowHandle, F: Fn(DragResult, CursorPosition) + Send +
                         this: &Object,
                         _: Sel,
                         dragging_session: id,
-                        context: NSUInteger,
+                        _context: NSUInteger,
                     ) -> NSUInteger {
                         unsafe {
                             let animates = this.get_ivar::<BOOL>("animate_on_cancel_or_failure");
                             let () = msg_send![dragging_session, setAnimatesToStartingPositionsOnCancelOrFail: *animates];
+
+                            *this.get_ivar::<NSUInteger>("operation_mask")
+                        }
+                    }
+
+                    extern "C" fn dragging_session_begin(
+                        this: &Object,
+                        _: Sel,
+                        _dragging_session: id,
+                        _at_point: NSPoint,
+                    ) {
+                        unsafe {
+                            let on_event = this.get_ivar::<*mut c_void>("on_event_ptr");
+                            if !on_event.is_null() {
+                                (*(*on_event as *mut OnEventCallback))(DragEvent::Started);
+                            }
                         }
+                    }
 
-                        if context == 0 {
-                            // NSDragOperationCopy
-                            1
-                        } else {
-                            // NSDragOperationEvery
-                            NSUInteger::MAX
+                    extern "C" fn dragging_session_moved(
+                        this: &Object,
+                        _: Sel,
+                        _dragging_session: id,
+                        moved_to_point: NSPoint,
+                    ) {
+                        unsafe {
+                            let on_event = this.get_ivar::<*mut c_void>("on_event_ptr");
+                            if !on_event.is_null() {
+                                (*(*on_event as *mut OnEventCallback))(DragEvent::Moved(
+                                    CursorPosition {
+                                        x: moved_to_point.x as i32,
+                                        y: CGDisplay::main().pixels_high() as i32
+                                            - moved_to_point.y as i32,
+                                    },
+                                ));
+                            }
                         }
                     }
 
@@ -257,17 +422,29 @@ pub fn start_drag<W: HasWindowHandle, F: Fn(DragResult, CursorPosition) + Send +
                                 y: CGDisplay::main().pixels_high() as i32 - ended_at_point.y as i32,
                             };
 
-                            let callback_closure =
-                                &*(*callback as *mut Box<dyn Fn(DragResult, CursorPosition)>);
+                            let callback_closure = &*(*callback as *mut OnDropCallback);
 
-                            if operation == 0 {
+                            let result = if operation == 0 {
                                 // NSDragOperationNone
-                                callback_closure(DragResult::Cancel, mouse_location);
+                                DragResult::Cancel(cancel_reason())
                             } else {
-                                callback_closure(DragResult::Dropped, mouse_location);
-                            }
+                                DragResult::Dropped(drag_operation(operation))
+                            };
+                            let window = *this.get_ivar::<id>("window");
+                            callback_closure(
+                                result,
+                                mouse_location,
+                                modifiers(),
+                                drop_destination(ended_at_point),
+                                drop_location(ended_at_point, window),
+                            );
 
-                            drop(Box::from_raw(*callback as *mut Box<dyn Fn(DragResult)>));
+                            drop(Box::from_raw(*callback as *mut OnDropCallback));
+
+                            let on_event = this.get_ivar::<*mut c_void>("on_event_ptr");
+                            if !on_event.is_null() {
+                                drop(Box::from_raw(*on_event as *mut OnEventCallback));
+                            }
                         }
                     }
 
@@ -279,20 +456,324 @@ pub fn start_drag<W: HasWindowHandle, F: Fn(DragResult, CursorPosition) + Send +
             let source: id = msg_send![cls, alloc];
             let source: id = msg_send![source, init];
 
-            let on_drop_callback =
-                Box::new(on_drop_callback) as Box<dyn Fn(DragResult, CursorPosition) + Send>;
+            let on_move_completed = options.on_move_completed;
+            let active_ = active.clone();
+            let on_drop_callback: OnDropCallback = Box::new(
+                move |result, cursor_position, modifiers, destination, drop_location| {
+                    // the destination performed the drop before the session ends, and the payload
+                    // is released when this callback is dropped
+                    let _payload = &payload;
+                    active_.store(false, Ordering::SeqCst);
+                    on_drop_callback(DragOutcome {
+                        result,
+                        cursor_position: Some(cursor_position),
+                        modifiers,
+                        duration: started_at.elapsed(),
+                        promised_file_path: None,
+                        destination: Some(destination),
+                        drop_location: Some(drop_location),
+                    });
+                    if let (DragResult::Dropped(DragOperation::Move), Some(on_move_completed)) =
+                        (result, &on_move_completed)
+                    {
+                        on_move_completed();
+                    }
+                },
+            );
             let callback_ptr = Box::into_raw(Box::new(on_drop_callback));
             (*source).set_ivar("on_drop_ptr", callback_ptr as *mut _ as *mut c_void);
+            let on_event_ptr = match options.on_event {
+                Some(on_event) => {
+                    Box::into_raw(Box::new(crate::throttle_events(on_event))) as *mut c_void
+                }
+                None => std::ptr::null_mut(),
+            };
+            (*source).set_ivar("on_event_ptr", on_event_ptr);
             (*source).set_ivar(
                 "animate_on_cancel_or_failure",
                 !options.skip_animatation_on_cancel_or_failure,
             );
+            (*source).set_ivar(
+                "operation_mask",
+                operation_mask(&options.allowed_operations),
+            );
+            (*source).set_ivar("window", window);
 
             let _: () = msg_send![ns_view, beginDraggingSessionWithItems: dragging_items event: drag_event source: source];
         }
 
-        Ok(())
+        Ok(crate::DragSession {
+            inner: DragSession { active },
+        })
     } else {
         Err(crate::Error::UnsupportedWindowHandle)
     }
 }
+
+/// Creates a `NSPasteboardItem` holding the static `data` and lazily providing the `types`.
+unsafe fn pasteboard_item(
+    data: Vec<(String, Vec<u8>)>,
+    provider: Option<DataProvider>,
+    types: Vec<String>,
+) -> id {
+    let item: id = msg_send![class!(NSPasteboardItem), alloc];
+    let item: id = msg_send![item, init];
+
+    for (data_type, bytes) in data {
+        let data = NSData::dataWithBytes_length_(
+            nil,
+            bytes.as_ptr() as *const std::os::raw::c_void,
+            bytes.len() as u64,
+        );
+        let _: () = msg_send![item, setData: data forType: NSString::new(&data_type).0];
+    }
+
+    if let Some(provider) = provider {
+        let cls = ClassDecl::new("DragRsDataProvider", class!(NSObject));
+        let cls = match cls {
+            Some(mut cls) => {
+                cls.add_ivar::<*mut c_void>("provider_ptr");
+                cls.add_protocol(Protocol::get("NSPasteboardItemDataProvider").unwrap());
+                cls.add_method(
+                    sel!(pasteboard:item:provideDataForType:),
+                    provide_data as extern "C" fn(&Object, Sel, id, id, id),
+                );
+                cls.add_method(
+                    sel!(pasteboardFinishedWithDataProvider:),
+                    pasteboard_finished as extern "C" fn(&Object, Sel, id),
+                );
+
+                extern "C" fn pasteboard_finished(this: &Object, _: Sel, _pasteboard: id) {
+                    unsafe {
+                        let provider = this.get_ivar::<*mut c_void>("provider_ptr");
+                        drop(Box::from_raw(
+                            *provider as *mut Box<dyn Fn(&str) -> Option<Vec<u8>>>,
+                        ));
+                    }
+                }
+
+                extern "C" fn provide_data(
+                    this: &Object,
+                    _: Sel,
+                    _pasteboard: id,
+                    item: id,
+                    data_type: id,
+                ) {
+                    unsafe {
+                        let provider = this.get_ivar::<*mut c_void>("provider_ptr");
+
+                        let provider = &*(*provider as *mut Box<dyn Fn(&str) -> Option<Vec<u8>>>);
+
+                        if let Some(data) = provider(NSString(data_type).to_str()) {
+                            let bytes = data.as_ptr() as *mut c_void;
+                            let length = data.len();
+                            let data: id = msg_send![class!(NSData), alloc];
+                            let data: id = msg_send![data, initWithBytesNoCopy:bytes length:length freeWhenDone: if length == 0 { NO } else { YES }];
+
+                            let _: () = msg_send![item, setData: data forType: data_type];
+                        }
+                    }
+                }
+
+                cls.register()
+            }
+            None => Class::get("DragRsDataProvider").expect("Failed to get the class definition"),
+        };
+
+        let data_provider: id = msg_send![cls, alloc];
+        let data_provider: id = msg_send![data_provider, init];
+
+        let provider_ptr = Box::into_raw(Box::new(provider));
+        (*data_provider).set_ivar("provider_ptr", provider_ptr as *mut _ as *mut c_void);
+
+        let types = types
+            .into_iter()
+            .map(|t| NSString::new(&t).0)
+            .collect::<Vec<id>>();
+        let _: () = msg_send![item, setDataProvider: data_provider forTypes: NSArray::arrayWithObjects(nil, &types)];
+    }
+
+    item
+}
+
+fn operation_mask(operations: &[DragOperation]) -> NSUInteger {
+    let mask = operations.iter().fold(0, |mask, operation| {
+        mask | match operation {
+            DragOperation::Copy => NS_DRAG_OPERATION_COPY,
+            DragOperation::Move => NS_DRAG_OPERATION_MOVE,
+            DragOperation::Link => NS_DRAG_OPERATION_LINK,
+            DragOperation::Ask => 0,
+        }
+    });
+    // copy is offered when no operation is allowed, matching the default options
+    if mask == 0 {
+        NS_DRAG_OPERATION_COPY
+    } else {
+        mask
+    }
+}
+
+fn drag_operation(operation: NSUInteger) -> DragOperation {
+    if operation & (NS_DRAG_OPERATION_MOVE | NS_DRAG_OPERATION_DELETE) != 0 {
+        DragOperation::Move
+    } else if operation & NS_DRAG_OPERATION_LINK != 0 {
+        DragOperation::Link
+    } else {
+        DragOperation::Copy
+    }
+}
+
+unsafe fn cancel_reason() -> CancelReason {
+    // the event ending the session is either the mouse up or the Escape key down
+    let event: id = msg_send![NSApp(), currentEvent];
+    if event != nil && event.eventType() == NSEventType::NSKeyDown {
+        CancelReason::UserCancelled
+    } else {
+        CancelReason::NoTarget
+    }
+}
+
+// windows of other applications only have a window number, while ours are found by it
+unsafe fn drop_destination(point: NSPoint) -> DropDestination {
+    let window_number: NSInteger = msg_send![class!(NSWindow), windowNumberAtPoint:point belowWindowWithWindowNumber:0 as NSInteger];
+    if window_number <= 0 {
+        return DropDestination::Desktop;
+    }
+    let window_id = Some(window_number as u64);
+    let window: id = msg_send![NSApp(), windowWithWindowNumber: window_number];
+    if window != nil {
+        return DropDestination::OwnWindow { window_id };
+    }
+
+    let (layer, pid) = window_info(window_number);
+    // the Finder desktop is drawn below the normal window layer
+    if layer.is_some_and(|layer| layer < 0) {
+        return DropDestination::Desktop;
+    }
+    let app_class = pid.and_then(|pid| {
+        let app: id = msg_send![class!(NSRunningApplication), runningApplicationWithProcessIdentifier: pid as i32];
+        let bundle_identifier: id = if app != nil {
+            msg_send![app, bundleIdentifier]
+        } else {
+            nil
+        };
+        (bundle_identifier != nil).then(|| NSString(bundle_identifier).to_str().to_string())
+    });
+    DropDestination::Foreign {
+        window_id,
+        app_class,
+        pid,
+    }
+}
+
+/// Returns the layer and the owner pid of a window of any application.
+unsafe fn window_info(window_number: NSInteger) -> (Option<NSInteger>, Option<u32>) {
+    let infos =
+        CGWindowListCopyWindowInfo(CG_WINDOW_LIST_OPTION_INCLUDING_WINDOW, window_number as u32);
+    if infos == nil {
+        return (None, None);
+    }
+    let info: id = msg_send![infos, firstObject];
+    let number = |key: &str| -> Option<NSInteger> {
+        if info == nil {
+            return None;
+        }
+        let value: id = msg_send![info, objectForKey: NSString::new(key).0];
+        (value != nil).then(|| msg_send![value, integerValue])
+    };
+    let layer = number("kCGWindowLayer");
+    let pid = number("kCGWindowOwnerPID").map(|pid| pid as u32);
+    let _: () = msg_send![infos, release];
+    (layer, pid)
+}
+
+// Cocoa positions are in points, with the origin at the bottom-left corner of the primary screen
+unsafe fn drop_location(point: NSPoint, window: id) -> DropLocation {
+    let screens: id = msg_send![class!(NSScreen), screens];
+    let count: NSUInteger = msg_send![screens, count];
+    let frames: Vec<(id, NSRect)> = (0..count)
+        .map(|index| {
+            let screen: id = msg_send![screens, objectAtIndex: index];
+            let frame: NSRect = msg_send![screen, frame];
+            (screen, frame)
+        })
+        .collect();
+    let primary_height = frames.first().map_or(0., |(_, frame)| frame.size.height);
+
+    let monitor = frames
+        .iter()
+        .find(|(_, frame)| {
+            (frame.origin.x..frame.origin.x + frame.size.width).contains(&point.x)
+                && (frame.origin.y..frame.origin.y + frame.size.height).contains(&point.y)
+        })
+        .map(|(screen, frame)| {
+            let scale_factor: f64 = msg_send![*screen, backingScaleFactor];
+            // `localizedName` requires macOS 10.15
+            let responds: BOOL = msg_send![*screen, respondsToSelector: sel!(localizedName)];
+            let name: id = if responds == YES {
+                msg_send![*screen, localizedName]
+            } else {
+                nil
+            };
+            MonitorInfo {
+                name: (name != nil).then(|| NSString(name).to_str().to_string()),
+                position: PhysicalPosition {
+                    x: (frame.origin.x * scale_factor).round() as i32,
+                    y: ((primary_height - frame.origin.y - frame.size.height) * scale_factor)
+                        .round() as i32,
+                },
+                size: PhysicalSize {
+                    width: (frame.size.width * scale_factor).round() as u32,
+                    height: (frame.size.height * scale_factor).round() as u32,
+                },
+                scale_factor,
+            }
+        });
+
+    let window_position = (window != nil).then(|| {
+        let frame: NSRect = msg_send![window, frame];
+        let content: NSRect = msg_send![window, contentRectForFrameRect: frame];
+        LogicalPosition {
+            x: point.x - content.origin.x,
+            y: content.origin.y + content.size.height - point.y,
+        }
+    });
+
+    DropLocation::from_logical(
+        LogicalPosition {
+            x: point.x,
+            y: primary_height - point.y,
+        },
+        window_position,
+        monitor,
+    )
+}
+
+unsafe fn modifiers() -> Modifiers {
+    let flags: NSUInteger = msg_send![class!(NSEvent), modifierFlags];
+    let flags = NSEventModifierFlags::from_bits_truncate(flags);
+    Modifiers {
+        shift: flags.contains(NSEventModifierFlags::NSShiftKeyMask),
+        control: flags.contains(NSEventModifierFlags::NSControlKeyMask),
+        alt: flags.contains(NSEventModifierFlags::NSAlternateKeyMask),
+        meta: flags.contains(NSEventModifierFlags::NSCommandKeyMask),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ask_only_falls_back_to_copy() {
+        assert_eq!(
+            operation_mask(&[DragOperation::Ask]),
+            NS_DRAG_OPERATION_COPY
+        );
+        assert_eq!(operation_mask(&[]), NS_DRAG_OPERATION_COPY);
+        assert_eq!(
+            operation_mask(&[DragOperation::Move, DragOperation::Ask]),
+            NS_DRAG_OPERATION_MOVE
+        );
+    }
+}
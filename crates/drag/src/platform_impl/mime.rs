@@ -0,0 +1,148 @@
+// Copyright 2023-2023 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+use super::uri;
+use crate::{DragItem, Error};
+
+const URI_LIST_TYPE: &str = "text/uri-list";
+const HTML_TYPE: &str = "text/html";
+const NETSCAPE_URL_TYPE: &str = "_NETSCAPE_URL";
+const MOZ_URL_TYPE: &str = "text/x-moz-url";
+// the targets GTK offers for text, including the legacy X11 selection targets
+const TEXT_TYPES: [&str; 5] = [
+    "text/plain;charset=utf-8",
+    "UTF8_STRING",
+    "text/plain",
+    "STRING",
+    "TEXT",
+];
+
+/// A drag item described as MIME types, for the native backends that do not go through GTK targets.
+pub struct MimeOffer {
+    item: DragItem,
+}
+
+impl MimeOffer {
+    pub fn new(item: DragItem) -> crate::Result<Self> {
+        // XDS needs a property on the source window that is only handled by the GTK backend
+        if let DragItem::PromisedFile { .. } = item {
+            return Err(Error::UnsupportedOperation);
+        }
+        Ok(Self { item })
+    }
+
+    /// The offered MIME types, in order of preference.
+    pub fn types(&self) -> Vec<String> {
+        let types: Vec<&str> = match &self.item {
+            DragItem::Files(_) => vec![URI_LIST_TYPE],
+            DragItem::Data { types, .. } => types.iter().map(String::as_str).collect(),
+            DragItem::Mixed {
+                files, data, types, ..
+            } => data
+                .iter()
+                .map(|(mime, _)| mime.as_str())
+                .chain(types.iter().map(String::as_str))
+                .chain((!files.is_empty()).then_some(URI_LIST_TYPE))
+                .collect(),
+            DragItem::Text(_) => TEXT_TYPES.to_vec(),
+            DragItem::Html { plain_fallback, .. } => std::iter::once(HTML_TYPE)
+                .chain(plain_fallback.iter().flat_map(|_| TEXT_TYPES))
+                .collect(),
+            DragItem::Url { .. } => [URI_LIST_TYPE, NETSCAPE_URL_TYPE, MOZ_URL_TYPE]
+                .into_iter()
+                .chain(TEXT_TYPES)
+                .collect(),
+            DragItem::PromisedFile { .. } => vec![],
+        };
+        types.into_iter().map(String::from).collect()
+    }
+
+    /// The data for one of the offered MIME types.
+    pub fn data(&self, mime: &str) -> Option<Vec<u8>> {
+        match &self.item {
+            DragItem::Files(paths) => {
+                (mime == URI_LIST_TYPE).then(|| uri::uri_list(paths).into_bytes())
+            }
+            DragItem::Data { provider, types } => types
+                .iter()
+                .any(|t| t == mime)
+                .then(|| provider(mime))
+                .flatten(),
+            DragItem::Mixed {
+                files,
+                data,
+                provider,
+                types,
+            } => {
+                if let Some((_, bytes)) = data.iter().find(|(t, _)| t == mime) {
+                    Some(bytes.clone())
+                } else if types.iter().any(|t| t == mime) {
+                    provider.as_ref().and_then(|provider| provider(mime))
+                } else if mime == URI_LIST_TYPE && !files.is_empty() {
+                    Some(uri::uri_list(files).into_bytes())
+                } else {
+                    None
+                }
+            }
+            DragItem::Text(text) => TEXT_TYPES
+                .contains(&mime)
+                .then(|| text.clone().into_bytes()),
+            DragItem::Html {
+                html,
+                plain_fallback,
+            } => {
+                if mime == HTML_TYPE {
+                    Some(html.clone().into_bytes())
+                } else if TEXT_TYPES.contains(&mime) {
+                    plain_fallback.clone().map(String::into_bytes)
+                } else {
+                    None
+                }
+            }
+            DragItem::Url { url, title } => match mime {
+                URI_LIST_TYPE => Some(format!("{url}\r\n").into_bytes()),
+                NETSCAPE_URL_TYPE => Some(uri::url_with_title(url, title.as_deref()).into_bytes()),
+                MOZ_URL_TYPE => Some(
+                    uri::url_with_title(url, title.as_deref())
+                        .encode_utf16()
+                        .flat_map(u16::to_ne_bytes)
+                        .collect(),
+                ),
+                mime if TEXT_TYPES.contains(&mime) => Some(url.clone().into_bytes()),
+                _ => None,
+            },
+            DragItem::PromisedFile { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_uri_list_is_crlf_terminated() {
+        let dir = std::env::temp_dir();
+        let offer = MimeOffer::new(DragItem::Files(vec![dir.clone()])).unwrap();
+        let uri = uri::file_uri(&std::fs::canonicalize(dir).unwrap());
+        assert_eq!(offer.types(), vec![URI_LIST_TYPE.to_string()]);
+        assert_eq!(
+            offer.data(URI_LIST_TYPE),
+            Some(format!("{uri}\r\n").into_bytes())
+        );
+        assert!(offer.data("text/plain").is_none());
+    }
+
+    #[test]
+    fn promised_files_are_unsupported() {
+        let item = DragItem::PromisedFile {
+            file_name: "file.txt".into(),
+            writer: Box::new(|_| Ok(())),
+        };
+        assert!(matches!(
+            MimeOffer::new(item),
+            Err(Error::UnsupportedOperation)
+        ));
+    }
+}
@@ -12,4 +12,17 @@ mod platform;
 #[path = "macos/mod.rs"]
 mod platform;
 
-pub use platform::start_drag;
+#[cfg(all(target_os = "linux", any(feature = "x11", feature = "wayland")))]
+mod mime;
+#[cfg(target_os = "linux")]
+mod uri;
+#[cfg(all(target_os = "linux", feature = "wayland"))]
+mod wayland;
+#[cfg(all(target_os = "linux", feature = "x11"))]
+mod x11;
+
+#[cfg(target_os = "linux")]
+pub use platform::{
+    prepare, register_drop_target_on_widget, start_drag_from_widget, PreparedWindow,
+};
+pub use platform::{register_drop_target, start_drag, DragSession, DropTarget};
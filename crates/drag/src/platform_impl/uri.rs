@@ -0,0 +1,88 @@
+// Copyright 2023-2023 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+use std::{fmt::Write, os::unix::ffi::OsStrExt, path::Path};
+
+/// Encodes an absolute path as a `file` URI with an empty authority, as defined by RFC 8089.
+///
+/// Every byte outside of the RFC 3986 `pchar` set (and the `/` separator) is percent-encoded,
+/// which keeps paths containing spaces, `#`, `%`, `?` or non UTF-8 bytes intact.
+pub fn file_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for &byte in path.as_os_str().as_bytes() {
+        if is_path_char(byte) {
+            uri.push(byte as char);
+        } else {
+            let _ = write!(uri, "%{byte:02X}");
+        }
+    }
+    uri
+}
+
+fn is_path_char(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric()
+        || matches!(
+            byte,
+            // unreserved
+            b'-' | b'.' | b'_' | b'~'
+            // sub-delims
+            | b'!' | b'$' | b'&' | b'\'' | b'(' | b')' | b'*' | b'+' | b',' | b';' | b'='
+            // pchar extras and the segment separator
+            | b':' | b'@' | b'/'
+        )
+}
+
+/// Formats a URL followed by its title on a second line, as `_NETSCAPE_URL` and `text/x-moz-url` expect.
+///
+/// The URL itself is used when there is no title.
+pub fn url_with_title(url: &str, title: Option<&str>) -> String {
+    format!("{url}\n{}", title.unwrap_or(url))
+}
+
+/// Builds a `text/uri-list` payload, with every URI terminated by CRLF as defined by RFC 2483.
+#[cfg(any(feature = "x11", feature = "wayland"))]
+pub fn uri_list<P: AsRef<Path>>(paths: &[P]) -> String {
+    paths
+        .iter()
+        .map(|path| format!("{}\r\n", file_uri(path.as_ref())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{ffi::OsStr, path::PathBuf};
+
+    #[test]
+    fn plain_paths_are_not_escaped() {
+        assert_eq!(
+            file_uri(Path::new("/home/user/file-1_2.txt")),
+            "file:///home/user/file-1_2.txt"
+        );
+    }
+
+    #[test]
+    fn reserved_characters_are_escaped() {
+        assert_eq!(
+            file_uri(Path::new("/tmp/my file #1 100% done?.txt")),
+            "file:///tmp/my%20file%20%231%20100%25%20done%3F.txt"
+        );
+    }
+
+    #[test]
+    fn non_utf8_bytes_are_escaped() {
+        let path = PathBuf::from(OsStr::from_bytes(b"/tmp/caf\xe9"));
+        assert_eq!(file_uri(&path), "file:///tmp/caf%E9");
+        assert_eq!(file_uri(Path::new("/tmp/café")), "file:///tmp/caf%C3%A9");
+    }
+
+    #[cfg(any(feature = "x11", feature = "wayland"))]
+    #[test]
+    fn uri_list_terminates_every_uri_with_crlf() {
+        assert_eq!(
+            uri_list(&["/tmp/a b", "/tmp/c"]),
+            "file:///tmp/a%20b\r\nfile:///tmp/c\r\n"
+        );
+    }
+}
@@ -0,0 +1,683 @@
+// Copyright 2023-2023 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+use std::{
+    ffi::c_void,
+    fs::File,
+    io::Write,
+    os::fd::AsFd,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc, Condvar, Mutex, Weak,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use gtk::gdk_pixbuf::Pixbuf;
+use rustix::event::{PollFd, PollFlags, Timespec};
+use wayland_client::{
+    backend::{Backend, ObjectId, WaylandError},
+    delegate_noop, event_created_child,
+    globals::{registry_queue_init, GlobalListContents},
+    protocol::{
+        wl_buffer::WlBuffer,
+        wl_callback::{self, WlCallback},
+        wl_compositor::WlCompositor,
+        wl_data_device::{self, WlDataDevice},
+        wl_data_device_manager::{DndAction, WlDataDeviceManager},
+        wl_data_offer::WlDataOffer,
+        wl_data_source::{self, WlDataSource},
+        wl_pointer::{self, WlPointer},
+        wl_registry::WlRegistry,
+        wl_seat::{self, WlSeat},
+        wl_shm::{self, WlShm},
+        wl_shm_pool::WlShmPool,
+        wl_surface::WlSurface,
+    },
+    Connection, Dispatch, EventQueue, Proxy, QueueHandle, WEnum,
+};
+
+use super::{
+    mime::MimeOffer,
+    platform::{image_to_pixbuf, premultiplied_argb, DragSession},
+};
+use crate::{
+    CancelReason, DragEvent, DragItem, DragOperation, DragOutcome, DragResult, Error, Image,
+    ImageAnchor, Modifiers, Options,
+};
+
+// the drag is driven by the compositor, so the queue only needs to be checked for its events
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+// how long the pointer tracking thread may take to catch up with the events already read
+const SYNC_TIMEOUT: Duration = Duration::from_secs(1);
+
+// one tracker per display, shared by its prepared windows and stopped with the last of them
+static TRACKERS: Mutex<Vec<(usize, Weak<PointerTracker>)>> = Mutex::new(Vec::new());
+
+/// Starts tracking the pointer button presses of the display, see [`crate::prepare`].
+///
+/// The tracking stops when the last returned tracker of the display is dropped.
+pub fn prepare(display: *mut c_void) -> crate::Result<Arc<PointerTracker>> {
+    let mut trackers = TRACKERS.lock().unwrap();
+    trackers.retain(|(_, tracker)| tracker.strong_count() > 0);
+    if let Some(tracker) = trackers
+        .iter()
+        .filter(|(d, _)| *d == display as usize)
+        .find_map(|(_, tracker)| tracker.upgrade())
+    {
+        return Ok(tracker);
+    }
+
+    let tracker = Arc::new(PointerTracker::new(display)?);
+    trackers.push((display as usize, Arc::downgrade(&tracker)));
+    Ok(tracker)
+}
+
+fn pointer_tracker(display: *mut c_void) -> Option<Arc<PointerTracker>> {
+    TRACKERS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(d, _)| *d == display as usize)
+        .find_map(|(_, tracker)| tracker.upgrade())
+}
+
+/// Starts a blocking drag out of a Wayland surface that is not managed by GTK.
+///
+/// The drag events are read on a queue of their own, and the calling thread does not return
+/// to its event loop until the drag ends.
+pub fn start_drag<F: Fn(DragOutcome) + Send + 'static>(
+    display: *mut c_void,
+    surface: *mut c_void,
+    item: DragItem,
+    image: Image,
+    on_drop_callback: F,
+    mut options: Options,
+) -> crate::Result<crate::DragSession> {
+    crate::validate::validate(&item, &image)?;
+    // released when the blocking drag returned
+    let _payload = crate::local::attach(options.payload.take());
+    let icon = image_to_pixbuf(&image).ok_or(Error::InvalidImage)?;
+    let offer = MimeOffer::new(item)?;
+
+    let started_at = Instant::now();
+    // without a prepared window no button press was observed
+    let tracker = pointer_tracker(display).ok_or(Error::FailedToStartDrag)?;
+    let conn = &tracker.conn;
+    // the compositor only accepts the serial of the button press holding the implicit grab
+    let (serial, releases) = tracker.button_press()?.ok_or(Error::FailedToStartDrag)?;
+
+    let (globals, mut queue) =
+        registry_queue_init::<DragState>(conn).map_err(|_| Error::FailedToStartDrag)?;
+    let qh = queue.handle();
+    // version 3 is required for the drag actions and the drop events
+    let manager: WlDataDeviceManager = globals
+        .bind(&qh, 3..=3, ())
+        .map_err(|_| Error::UnsupportedOperation)?;
+    let compositor: WlCompositor = globals
+        .bind(&qh, 1..=4, ())
+        .map_err(|_| Error::UnsupportedOperation)?;
+    let shm: WlShm = globals
+        .bind(&qh, 1..=1, ())
+        .map_err(|_| Error::UnsupportedOperation)?;
+    let origin = unsafe { ObjectId::from_ptr(WlSurface::interface(), surface.cast()) }
+        .and_then(|id| WlSurface::from_id(conn, id))
+        .map_err(|_| Error::UnsupportedWindowHandle)?;
+
+    let source = manager.create_data_source(&qh, ());
+    for mime in offer.types() {
+        source.offer(mime);
+    }
+    source.set_actions(dnd_actions(&options.allowed_operations));
+    let device = manager.get_data_device(&tracker.seat, &qh, ());
+    let icon = IconSurface::new(&compositor, &shm, &qh, &icon, options.image_anchor)?;
+
+    device.start_drag(
+        Some(&source),
+        &origin,
+        icon.as_ref().map(|icon| &icon.surface),
+        serial,
+    );
+    if let Some(icon) = &icon {
+        icon.commit();
+    }
+    conn.flush().map_err(|_| Error::FailedToStartDrag)?;
+
+    let mut state = DragState {
+        offer,
+        on_event: options.on_event.take(),
+        action: None,
+        accepted: false,
+        dropped: false,
+        result: None,
+    };
+    state.emit(DragEvent::Started);
+    let result = loop {
+        queue.dispatch_pending(&mut state)?;
+        if let Some(result) = state.result.take() {
+            break result;
+        }
+        // the compositor silently ignores a drag it refused, which only shows as the button being released
+        if tracker.releases() != releases {
+            break DragResult::Cancel(CancelReason::Error);
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    source.destroy();
+    device.release();
+    if let Some(icon) = icon {
+        icon.destroy();
+    }
+    let _ = conn.flush();
+
+    if let DragResult::Dropped(DragOperation::Move) = result {
+        if let Some(on_move_completed) = &options.on_move_completed {
+            on_move_completed();
+        }
+    }
+    on_drop_callback(DragOutcome {
+        result,
+        cursor_position: None,
+        modifiers: Modifiers::default(),
+        duration: started_at.elapsed(),
+        promised_file_path: None,
+        destination: None,
+        drop_location: None,
+    });
+
+    Ok(crate::DragSession {
+        inner: DragSession::Native,
+    })
+}
+
+struct DragState {
+    offer: MimeOffer,
+    on_event: Option<Box<dyn Fn(DragEvent) + Send>>,
+    action: Option<DragOperation>,
+    accepted: bool,
+    dropped: bool,
+    result: Option<DragResult>,
+}
+
+impl DragState {
+    fn emit(&self, event: DragEvent) {
+        if let Some(on_event) = &self.on_event {
+            on_event(event);
+        }
+    }
+}
+
+impl Dispatch<WlDataSource, ()> for DragState {
+    fn event(
+        state: &mut Self,
+        _source: &WlDataSource,
+        event: wl_data_source::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_data_source::Event::Send { mime_type, fd } => {
+                // the target reads until the fd is closed, which happens when the file is dropped
+                if let Some(data) = state.offer.data(&mime_type) {
+                    let _ = File::from(fd).write_all(&data);
+                }
+            }
+            wl_data_source::Event::Target { mime_type } => state.accepted = mime_type.is_some(),
+            wl_data_source::Event::Action { dnd_action } => {
+                let action = match dnd_action {
+                    WEnum::Value(action) => drag_operation(action),
+                    WEnum::Unknown(_) => None,
+                };
+                if action != state.action {
+                    state.action = action;
+                    state.emit(DragEvent::ActionChanged(action));
+                }
+            }
+            wl_data_source::Event::DndDropPerformed => state.dropped = true,
+            wl_data_source::Event::DndFinished => {
+                state.result = Some(DragResult::Dropped(
+                    state.action.unwrap_or(DragOperation::Copy),
+                ));
+            }
+            // also sent when the drop was not accepted, or when the user pressed Escape
+            wl_data_source::Event::Cancelled => {
+                state.result = Some(DragResult::Cancel(if state.dropped && state.accepted {
+                    CancelReason::Error
+                } else {
+                    CancelReason::NoTarget
+                }));
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlDataDevice, ()> for DragState {
+    fn event(
+        _state: &mut Self,
+        _device: &WlDataDevice,
+        _event: wl_data_device::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+
+    // the device also receives the offers of the selection and of the drags over this client
+    event_created_child!(DragState, WlDataDevice, [
+        wl_data_device::EVT_DATA_OFFER_OPCODE => (WlDataOffer, ()),
+    ]);
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for DragState {
+    fn event(
+        _state: &mut Self,
+        _registry: &WlRegistry,
+        _event: <WlRegistry as Proxy>::Event,
+        _data: &GlobalListContents,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+delegate_noop!(DragState: WlDataDeviceManager);
+delegate_noop!(DragState: WlCompositor);
+delegate_noop!(DragState: WlShmPool);
+delegate_noop!(DragState: ignore WlShm);
+delegate_noop!(DragState: ignore WlBuffer);
+delegate_noop!(DragState: ignore WlSurface);
+delegate_noop!(DragState: ignore WlDataOffer);
+
+/// The drag icon, a surface backed by a shared memory buffer.
+struct IconSurface {
+    surface: WlSurface,
+    buffer: WlBuffer,
+    width: i32,
+    height: i32,
+    // the point of the icon held under the pointer
+    hotspot: (i32, i32),
+}
+
+impl IconSurface {
+    fn new(
+        compositor: &WlCompositor,
+        shm: &WlShm,
+        qh: &QueueHandle<DragState>,
+        pixbuf: &Pixbuf,
+        anchor: Option<ImageAnchor>,
+    ) -> crate::Result<Option<Self>> {
+        let (width, height) = (pixbuf.width(), pixbuf.height());
+        let (hot_x, hot_y) = anchor.map_or((0., 0.), |anchor| {
+            anchor.offset(f64::from(width), f64::from(height))
+        });
+        // ARGB8888 is always little-endian
+        let pixels = premultiplied_argb(pixbuf, true);
+        let Ok(size) = i32::try_from(pixels.len()) else {
+            return Ok(None);
+        };
+
+        let fd = rustix::fs::memfd_create("drag-icon", rustix::fs::MemfdFlags::CLOEXEC)
+            .map_err(std::io::Error::from)?;
+        let mut file = File::from(fd);
+        file.write_all(&pixels)?;
+
+        let pool = shm.create_pool(file.as_fd(), size, qh, ());
+        let buffer = pool.create_buffer(
+            0,
+            width,
+            height,
+            width * 4,
+            wl_shm::Format::Argb8888,
+            qh,
+            (),
+        );
+        pool.destroy();
+
+        Ok(Some(Self {
+            surface: compositor.create_surface(qh, ()),
+            buffer,
+            width,
+            height,
+            hotspot: (hot_x.round() as i32, hot_y.round() as i32),
+        }))
+    }
+
+    // the buffer is attached once the surface has the drag icon role, its top-left corner
+    // starts at the pointer and the attach offset moves the hotspot there instead
+    fn commit(&self) {
+        self.surface
+            .attach(Some(&self.buffer), -self.hotspot.0, -self.hotspot.1);
+        self.surface.damage(0, 0, self.width, self.height);
+        self.surface.commit();
+    }
+
+    fn destroy(self) {
+        self.surface.destroy();
+        self.buffer.destroy();
+    }
+}
+
+/// Observes the button presses of the display through a `wl_pointer` of its own.
+///
+/// The compositor sends the pointer events to every `wl_pointer` of the client,
+/// so the serial of the press starting the drag is known without access to the application pointer.
+pub struct PointerTracker {
+    conn: Connection,
+    seat: WlSeat,
+    qh: QueueHandle<TrackerState>,
+    shared: Arc<TrackerShared>,
+    next_sync: AtomicU32,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+#[derive(Default)]
+struct TrackerShared {
+    buttons: Mutex<Buttons>,
+    synced: Condvar,
+}
+
+#[derive(Default)]
+struct Buttons {
+    pressed: u32,
+    last_press_serial: Option<u32>,
+    releases: u32,
+    synced: u32,
+}
+
+struct TrackerState {
+    shared: Arc<TrackerShared>,
+    pointer: Option<WlPointer>,
+}
+
+impl PointerTracker {
+    fn new(display: *mut c_void) -> crate::Result<Self> {
+        let conn =
+            Connection::from_backend(unsafe { Backend::from_foreign_display(display.cast()) });
+        let (globals, mut queue) =
+            registry_queue_init::<TrackerState>(&conn).map_err(|_| Error::FailedToStartDrag)?;
+        let qh = queue.handle();
+        let seat: WlSeat = globals
+            .bind(&qh, 1..=7, ())
+            .map_err(|_| Error::UnsupportedOperation)?;
+
+        let shared = Arc::new(TrackerShared::default());
+        let mut state = TrackerState {
+            shared: shared.clone(),
+            pointer: None,
+        };
+        queue.roundtrip(&mut state)?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = thread::spawn({
+            let stop = stop.clone();
+            move || track(queue, state, stop)
+        });
+
+        Ok(Self {
+            conn,
+            seat,
+            qh,
+            shared,
+            next_sync: AtomicU32::new(0),
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// The serial of the last button press while a button is held, with the number of releases seen so far.
+    fn button_press(&self) -> crate::Result<Option<(u32, u32)>> {
+        // the tracking thread might not have dispatched the press that started the drag yet,
+        // a sync request is answered after every event already sent by the compositor
+        let token = self.next_sync.fetch_add(1, Ordering::Relaxed) + 1;
+        self.conn.display().sync(&self.qh, token);
+        self.conn.flush().map_err(|_| Error::FailedToStartDrag)?;
+
+        let buttons = self.shared.buttons.lock().unwrap();
+        let (buttons, _) = self
+            .shared
+            .synced
+            .wait_timeout_while(buttons, SYNC_TIMEOUT, |buttons| buttons.synced < token)
+            .unwrap();
+        Ok(buttons
+            .last_press_serial
+            .filter(|_| buttons.pressed > 0)
+            .map(|serial| (serial, buttons.releases)))
+    }
+
+    fn releases(&self) -> u32 {
+        self.shared.buttons.lock().unwrap().releases
+    }
+}
+
+impl Drop for PointerTracker {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// reads the events of the display until the tracker is dropped, checking for it every poll interval
+fn track(mut queue: EventQueue<TrackerState>, mut state: TrackerState, stop: Arc<AtomicBool>) {
+    let timeout = Timespec::try_from(POLL_INTERVAL).unwrap();
+    while !stop.load(Ordering::Relaxed) {
+        if queue.dispatch_pending(&mut state).is_err() || queue.flush().is_err() {
+            break;
+        }
+        // fails while events of the queue are still pending
+        let Some(guard) = queue.prepare_read() else {
+            continue;
+        };
+        let mut fds = [PollFd::from_borrowed_fd(
+            guard.connection_fd(),
+            PollFlags::IN,
+        )];
+        match rustix::event::poll(&mut fds, Some(&timeout)) {
+            // dropping the guard cancels the read
+            Ok(0) | Err(rustix::io::Errno::INTR) => {}
+            Ok(_) => match guard.read() {
+                Ok(_) => {}
+                Err(WaylandError::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(_) => break,
+            },
+            Err(_) => break,
+        }
+    }
+}
+
+impl Dispatch<WlSeat, ()> for TrackerState {
+    fn event(
+        state: &mut Self,
+        seat: &WlSeat,
+        event: wl_seat::Event,
+        _data: &(),
+        _conn: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wl_seat::Event::Capabilities {
+            capabilities: WEnum::Value(capabilities),
+        } = event
+        {
+            if capabilities.contains(wl_seat::Capability::Pointer) && state.pointer.is_none() {
+                state.pointer = Some(seat.get_pointer(qh, ()));
+            }
+        }
+    }
+}
+
+impl Dispatch<WlPointer, ()> for TrackerState {
+    fn event(
+        state: &mut Self,
+        _pointer: &WlPointer,
+        event: wl_pointer::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        let mut buttons = state.shared.buttons.lock().unwrap();
+        match event {
+            wl_pointer::Event::Button {
+                serial,
+                state: WEnum::Value(wl_pointer::ButtonState::Pressed),
+                ..
+            } => {
+                buttons.pressed += 1;
+                buttons.last_press_serial = Some(serial);
+            }
+            wl_pointer::Event::Button {
+                state: WEnum::Value(wl_pointer::ButtonState::Released),
+                ..
+            } => {
+                buttons.pressed = buttons.pressed.saturating_sub(1);
+                buttons.releases = buttons.releases.wrapping_add(1);
+            }
+            // no button events are sent without the pointer focus, which a started drag also takes away
+            wl_pointer::Event::Leave { .. } => {
+                buttons.pressed = 0;
+                buttons.last_press_serial = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlCallback, u32> for TrackerState {
+    fn event(
+        state: &mut Self,
+        _callback: &WlCallback,
+        event: wl_callback::Event,
+        token: &u32,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let wl_callback::Event::Done { .. } = event {
+            let mut buttons = state.shared.buttons.lock().unwrap();
+            buttons.synced = buttons.synced.max(*token);
+            state.shared.synced.notify_all();
+        }
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for TrackerState {
+    fn event(
+        _state: &mut Self,
+        _registry: &WlRegistry,
+        _event: <WlRegistry as Proxy>::Event,
+        _data: &GlobalListContents,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+fn dnd_actions(operations: &[DragOperation]) -> DndAction {
+    let actions = operations
+        .iter()
+        .fold(DndAction::empty(), |actions, operation| {
+            actions
+                | match operation {
+                    DragOperation::Copy => DndAction::Copy,
+                    DragOperation::Move => DndAction::Move,
+                    DragOperation::Ask => DndAction::Ask,
+                    // links are not part of the core protocol
+                    DragOperation::Link => DndAction::empty(),
+                }
+        });
+    // copy is offered when no operation is allowed, matching the default options
+    if actions.is_empty() {
+        DndAction::Copy
+    } else {
+        actions
+    }
+}
+
+fn drag_operation(action: DndAction) -> Option<DragOperation> {
+    if action.contains(DndAction::Move) {
+        Some(DragOperation::Move)
+    } else if action.contains(DndAction::Ask) {
+        Some(DragOperation::Ask)
+    } else if action.contains(DndAction::Copy) {
+        Some(DragOperation::Copy)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // run under a headless compositor, e.g. `weston --backend=headless`, with `cargo test -- --ignored`
+    #[test]
+    #[ignore = "requires a Wayland compositor"]
+    fn no_button_press_without_pointer_input() {
+        let conn = Connection::connect_to_env().unwrap();
+        let tracker = prepare(conn.backend().display_ptr().cast()).unwrap();
+        assert!(tracker.button_press().unwrap().is_none());
+    }
+
+    #[test]
+    #[ignore = "requires a Wayland compositor"]
+    fn writes_the_requested_type_to_the_fd() {
+        use super::super::uri;
+        use std::{io::Read, os::unix::net::UnixStream};
+
+        let conn = Connection::connect_to_env().unwrap();
+        let (globals, mut queue) = registry_queue_init::<DragState>(&conn).unwrap();
+        let qh = queue.handle();
+        let manager: WlDataDeviceManager = globals.bind(&qh, 3..=3, ()).unwrap();
+
+        let dir = std::fs::canonicalize(std::env::temp_dir()).unwrap();
+        let offer = MimeOffer::new(DragItem::Mixed {
+            files: vec![dir.clone()],
+            data: Vec::new(),
+            provider: Some(Box::new(|mime| Some(mime.as_bytes().to_vec()))),
+            types: vec!["application/x-drag-rs".into()],
+        })
+        .unwrap();
+        let source = manager.create_data_source(&qh, ());
+        for mime in offer.types() {
+            source.offer(mime);
+        }
+        let mut state = DragState {
+            offer,
+            on_event: None,
+            action: None,
+            accepted: false,
+            dropped: false,
+            result: None,
+        };
+        queue.roundtrip(&mut state).unwrap();
+
+        // the compositor hands over one end of a pipe the drop target reads from
+        let mut receive = |mime_type: &str| {
+            let (mut target, fd) = UnixStream::pair().unwrap();
+            <DragState as Dispatch<WlDataSource, ()>>::event(
+                &mut state,
+                &source,
+                wl_data_source::Event::Send {
+                    mime_type: mime_type.into(),
+                    fd: fd.into(),
+                },
+                &(),
+                &conn,
+                &qh,
+            );
+            let mut data = Vec::new();
+            target.read_to_end(&mut data).unwrap();
+            data
+        };
+        assert_eq!(
+            receive("text/uri-list"),
+            format!("{}\r\n", uri::file_uri(&dir)).into_bytes()
+        );
+        assert_eq!(receive("application/x-drag-rs"), b"application/x-drag-rs");
+        assert!(receive("text/plain").is_empty());
+    }
+}
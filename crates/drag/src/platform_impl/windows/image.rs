@@ -56,6 +56,29 @@ pub(crate) fn read_path_to_hbitmap(path: &Path) -> Result<HBITMAP> {
     }
 }
 
+/// Builds a bitmap from non-premultiplied RGBA pixels,
+/// converting them to the premultiplied BGRA layout expected by the drag image.
+pub(crate) fn rgba_to_hbitmap(width: u32, height: u32, pixels: &[u8]) -> Result<HBITMAP> {
+    let pixel_buf: Vec<u8> = pixels
+        .chunks_exact(4)
+        .flat_map(|pixel| {
+            let [r, g, b, a] = [pixel[0], pixel[1], pixel[2], pixel[3]];
+            let premultiply = |c: u8| ((c as u16 * a as u16 + 127) / 255) as u8;
+            [premultiply(b), premultiply(g), premultiply(r), a]
+        })
+        .collect();
+
+    unsafe {
+        Ok(CreateBitmap(
+            width as i32,
+            height as i32,
+            1,
+            32,
+            Some(pixel_buf.as_ptr() as *const c_void),
+        ))
+    }
+}
+
 fn decoder_to_hbitmap(decoder: IWICBitmapDecoder) -> Result<HBITMAP> {
     unsafe {
         let frame = decoder.GetFrame(0)?;
@@ -2,33 +2,51 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-License-Identifier: MIT
 
-use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+use raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawWindowHandle};
 
-use crate::{CursorPosition, DragItem, DragResult, Image, Options};
+use crate::{
+    CancelReason, CursorPosition, DragEvent, DragItem, DragOperation, DragOutcome, DragResult,
+    DropDestination, DropEvent, DropLocation, DropResponse, Image, ImageAnchor, Modifiers,
+    MonitorInfo, Options, PhysicalPosition, PhysicalSize,
+};
 
 use std::{
+    cell::Cell,
     ffi::c_void,
     iter::once,
     os::windows::ffi::OsStrExt,
     path::{Path, PathBuf},
+    rc::Rc,
     sync::Once,
+    time::Instant,
 };
 use windows::{
     core::*,
     Win32::{
         Foundation::*,
-        Graphics::Gdi::{GetObjectW, BITMAP},
+        Graphics::Gdi::{
+            GetMonitorInfoW, GetObjectW, MonitorFromPoint, ScreenToClient, BITMAP, HMONITOR,
+            MONITORINFO, MONITORINFOEXW, MONITOR_DEFAULTTONULL,
+        },
         System::Com::*,
         System::Memory::*,
         System::Ole::{DoDragDrop, OleInitialize},
-        System::Ole::{IDropSource, IDropSource_Impl, CF_HDROP, DROPEFFECT, DROPEFFECT_COPY},
-        System::SystemServices::{MK_LBUTTON, MODIFIERKEYS_FLAGS},
+        System::Ole::{
+            IDropSource, IDropSource_Impl, CF_HDROP, DROPEFFECT, DROPEFFECT_COPY, DROPEFFECT_LINK,
+            DROPEFFECT_MOVE, DROPEFFECT_NONE, MK_ALT,
+        },
+        System::SystemServices::{MK_CONTROL, MK_LBUTTON, MK_SHIFT, MODIFIERKEYS_FLAGS},
+        System::Threading::GetCurrentProcessId,
         UI::{
+            HiDpi::{GetDpiForMonitor, MDT_EFFECTIVE_DPI},
             Shell::{
                 BHID_DataObject, CLSID_DragDropHelper, Common, IDragSourceHelper, IShellItemArray,
                 SHCreateDataObject, SHCreateShellItemArrayFromIDLists, DROPFILES, SHDRAGIMAGE,
             },
-            WindowsAndMessaging::GetCursorPos,
+            WindowsAndMessaging::{
+                GetAncestor, GetClassNameW, GetCursorPos, GetShellWindow, GetWindowThreadProcessId,
+                WindowFromPoint, GA_ROOT,
+            },
         },
     },
 };
@@ -53,21 +71,94 @@ struct DataObject {
     inner_shell_obj: IDataObject,
 }
 
+// `DoDragDrop` blocks until the drag ends, so there's never a drag in flight to act on
+pub struct DragSession;
+
+impl DragSession {
+    pub fn cancel(&self) -> crate::Result<()> {
+        Ok(())
+    }
+
+    pub fn is_active(&self) -> bool {
+        false
+    }
+
+    pub fn set_image(&self, _image: Image) -> crate::Result<()> {
+        Ok(())
+    }
+}
+
+/// State reported by the drop source while the drag is running.
+#[derive(Default)]
+struct DragState {
+    escape_pressed: Cell<bool>,
+    key_state: Cell<MODIFIERKEYS_FLAGS>,
+    on_event: Option<Box<dyn Fn(DragEvent) + Send>>,
+    last_position: Cell<Option<(i32, i32)>>,
+    last_effect: Cell<Option<DROPEFFECT>>,
+}
+
+impl DragState {
+    fn new(on_event: Option<Box<dyn Fn(DragEvent) + Send>>) -> Self {
+        Self {
+            on_event: on_event.map(crate::throttle_events),
+            ..Default::default()
+        }
+    }
+
+    fn update(&self, fescapepressed: BOOL, grfkeystate: MODIFIERKEYS_FLAGS) {
+        self.escape_pressed.set(fescapepressed.as_bool());
+        self.key_state.set(grfkeystate);
+
+        let mut pt = POINT { x: 0, y: 0 };
+        if unsafe { GetCursorPos(&mut pt) }.is_ok()
+            && self.last_position.replace(Some((pt.x, pt.y))) != Some((pt.x, pt.y))
+        {
+            self.emit(DragEvent::Moved(CursorPosition { x: pt.x, y: pt.y }));
+        }
+    }
+
+    fn feedback(&self, effect: DROPEFFECT) {
+        if self.last_effect.replace(Some(effect)) != Some(effect) {
+            self.emit(DragEvent::ActionChanged(
+                (effect != DROPEFFECT_NONE).then(|| drag_operation(effect)),
+            ));
+        }
+    }
+
+    fn emit(&self, event: DragEvent) {
+        if let Some(on_event) = &self.on_event {
+            on_event(event);
+        }
+    }
+
+    fn modifiers(&self) -> Modifiers {
+        let key_state = self.key_state.get();
+        Modifiers {
+            shift: key_state & MK_SHIFT != MODIFIERKEYS_FLAGS(0),
+            control: key_state & MK_CONTROL != MODIFIERKEYS_FLAGS(0),
+            alt: key_state & MODIFIERKEYS_FLAGS(MK_ALT) != MODIFIERKEYS_FLAGS(0),
+            meta: false,
+        }
+    }
+}
+
 #[implement(IDropSource)]
-struct DropSource(());
+struct DropSource(Rc<DragState>);
 
 #[implement(IDropSource)]
-struct DummyDropSource(());
+struct DummyDropSource(Rc<DragState>);
 
 impl DropSource {
-    fn new() -> Self {
-        Self(())
+    fn new(state: Rc<DragState>) -> Self {
+        Self(state)
     }
 }
 
 #[allow(non_snake_case)]
 impl IDropSource_Impl for DropSource {
     fn QueryContinueDrag(&self, fescapepressed: BOOL, grfkeystate: MODIFIERKEYS_FLAGS) -> HRESULT {
+        self.0.update(fescapepressed, grfkeystate);
         if fescapepressed.as_bool() {
             DRAGDROP_S_CANCEL
         } else if (grfkeystate & MK_LBUTTON) == MODIFIERKEYS_FLAGS(0) {
@@ -77,20 +168,22 @@ impl IDropSource_Impl for DropSource {
         }
     }
 
-    fn GiveFeedback(&self, _dweffect: DROPEFFECT) -> HRESULT {
+    fn GiveFeedback(&self, dweffect: DROPEFFECT) -> HRESULT {
+        self.0.feedback(dweffect);
         DRAGDROP_S_USEDEFAULTCURSORS
     }
 }
 
 impl DummyDropSource {
-    fn new() -> Self {
-        Self(())
+    fn new(state: Rc<DragState>) -> Self {
+        Self(state)
     }
 }
 
 #[allow(non_snake_case)]
 impl IDropSource_Impl for DummyDropSource {
     fn QueryContinueDrag(&self, fescapepressed: BOOL, grfkeystate: MODIFIERKEYS_FLAGS) -> HRESULT {
+        self.0.update(fescapepressed, grfkeystate);
         if fescapepressed.as_bool() || (grfkeystate & MK_LBUTTON) == MODIFIERKEYS_FLAGS(0) {
             DRAGDROP_S_CANCEL
         } else {
@@ -98,7 +191,8 @@ impl IDropSource_Impl for DummyDropSource {
         }
     }
 
-    fn GiveFeedback(&self, _dweffect: DROPEFFECT) -> HRESULT {
+    fn GiveFeedback(&self, dweffect: DROPEFFECT) -> HRESULT {
+        self.0.feedback(dweffect);
         DRAGDROP_S_USEDEFAULTCURSORS
     }
 }
@@ -210,14 +304,44 @@ impl IDataObject_Impl for DataObject {
     }
 }
 
-pub fn start_drag<W: HasWindowHandle, F: Fn(DragResult, CursorPosition) + Send + 'static>(
+pub struct DropTarget;
+
+impl DropTarget {
+    pub fn unregister(self) -> crate::Result<()> {
+        Ok(())
+    }
+}
+
+pub fn register_drop_target<W: HasWindowHandle, F: Fn(DropEvent) -> DropResponse + 'static>(
+    _handle: &W,
+    _handler: F,
+) -> crate::Result<crate::DropTarget> {
+    Err(crate::Error::UnsupportedOperation)
+}
+
+pub fn start_drag<W: HasWindowHandle + HasDisplayHandle, F: Fn(DragOutcome) + Send + 'static>(
     handle: &W,
     item: DragItem,
     image: Image,
     on_drop_callback: F,
-    _options: Options,
-) -> crate::Result<()> {
-    if let Ok(RawWindowHandle::Win32(_w)) = handle.window_handle().map(|h| h.as_raw()) {
+    mut options: Options,
+) -> crate::Result<crate::DragSession> {
+    crate::validate::validate(&item, &image)?;
+    // drop targets of this process receive the drop within `DoDragDrop`, which returns once the drag ended
+    let _payload = crate::local::attach(options.payload.take());
+
+    let started_at = Instant::now();
+    if let Ok(RawWindowHandle::Win32(w)) = handle.window_handle().map(|h| h.as_raw()) {
+        let hwnd = HWND(w.hwnd.get());
+        let drag_state = Rc::new(DragState::new(options.on_event.take()));
+        let allowed_effects = drop_effects(&options.allowed_operations);
+
+        // only the files of a mixed item can be offered for now
+        let item = match item {
+            DragItem::Mixed { files, .. } if !files.is_empty() => DragItem::Files(files),
+            item => item,
+        };
+
         match item {
             DragItem::Files(files) => {
                 init_ole();
@@ -228,41 +352,53 @@ pub fn start_drag<W: HasWindowHandle, F: Fn(DragResult, CursorPosition) + Send +
                     }
                 }
 
+                // decode the image before building the shell data object, so an invalid image fails early
+                let drag_image = get_drag_image(image, options.image_anchor)?;
+
                 let mut paths = Vec::new();
                 for f in files {
                     paths.push(dunce::canonicalize(f)?);
                 }
 
-                let data_object: IDataObject = get_file_data_object(&paths).unwrap();
-                let drop_source: IDropSource = DropSource::new().into();
+                let data_object: IDataObject = get_file_data_object(&paths)?;
+                let drop_source: IDropSource = DropSource::new(drag_state.clone()).into();
 
                 unsafe {
-                    if let Some(drag_image) = get_drag_image(image) {
-                        if let Ok(helper) =
-                            create_instance::<IDragSourceHelper>(&CLSID_DragDropHelper)
-                        {
-                            let _ = helper.InitializeFromBitmap(&drag_image, &data_object);
-                        }
+                    if let Ok(helper) = create_instance::<IDragSourceHelper>(&CLSID_DragDropHelper)
+                    {
+                        let _ = helper.InitializeFromBitmap(&drag_image, &data_object);
                     }
 
                     let mut out_dropeffect = DROPEFFECT::default();
+                    drag_state.emit(DragEvent::Started);
                     let drop_result = DoDragDrop(
                         &data_object,
                         &drop_source,
-                        DROPEFFECT_COPY,
+                        allowed_effects,
                         &mut out_dropeffect,
                     );
-                    let mut pt = POINT { x: 0, y: 0 };
-                    GetCursorPos(&mut pt)?;
-                    if drop_result == DRAGDROP_S_DROP {
-                        on_drop_callback(DragResult::Dropped, CursorPosition { x: pt.x, y: pt.y });
-                    } else {
-                        // DRAGDROP_S_CANCEL
-                        on_drop_callback(DragResult::Cancel, CursorPosition { x: pt.x, y: pt.y });
+                    let outcome = get_drag_outcome(
+                        hwnd,
+                        drop_result,
+                        out_dropeffect,
+                        &drag_state,
+                        started_at,
+                    )?;
+                    let result = outcome.result;
+                    on_drop_callback(outcome);
+                    if let (DragResult::Dropped(DragOperation::Move), Some(on_move_completed)) =
+                        (result, &options.on_move_completed)
+                    {
+                        on_move_completed();
                     }
                 }
             }
-            DragItem::Data { .. } => {
+            DragItem::Data { .. }
+            | DragItem::Mixed { .. }
+            | DragItem::Text(_)
+            | DragItem::Html { .. }
+            | DragItem::Url { .. }
+            | DragItem::PromisedFile { .. } => {
                 init_ole();
                 unsafe {
                     #[allow(static_mut_refs)]
@@ -271,50 +407,222 @@ pub fn start_drag<W: HasWindowHandle, F: Fn(DragResult, CursorPosition) + Send +
                     }
                 }
 
+                // decode the image before building the shell data object, so an invalid image fails early
+                let drag_image = get_drag_image(image, options.image_anchor)?;
+
                 let paths = vec![dunce::canonicalize("./")?];
 
-                let data_object: IDataObject = get_file_data_object(&paths).unwrap();
-                let drop_source: IDropSource = DummyDropSource::new().into();
+                let data_object: IDataObject = get_file_data_object(&paths)?;
+                let drop_source: IDropSource = DummyDropSource::new(drag_state.clone()).into();
 
                 unsafe {
-                    if let Some(drag_image) = get_drag_image(image) {
-                        if let Ok(helper) =
-                            create_instance::<IDragSourceHelper>(&CLSID_DragDropHelper)
-                        {
-                            let _ = helper.InitializeFromBitmap(&drag_image, &data_object);
-                        }
+                    if let Ok(helper) = create_instance::<IDragSourceHelper>(&CLSID_DragDropHelper)
+                    {
+                        let _ = helper.InitializeFromBitmap(&drag_image, &data_object);
                     }
 
                     let mut out_dropeffect = DROPEFFECT::default();
+                    drag_state.emit(DragEvent::Started);
                     let drop_result = DoDragDrop(
                         &data_object,
                         &drop_source,
-                        DROPEFFECT_COPY,
+                        allowed_effects,
                         &mut out_dropeffect,
                     );
-                    let mut pt = POINT { x: 0, y: 0 };
-                    GetCursorPos(&mut pt)?;
-                    if drop_result == DRAGDROP_S_DROP {
-                        on_drop_callback(DragResult::Dropped, CursorPosition { x: pt.x, y: pt.y });
-                    } else {
-                        // DRAGDROP_S_CANCEL
-                        on_drop_callback(DragResult::Cancel, CursorPosition { x: pt.x, y: pt.y });
-                    }
+                    on_drop_callback(get_drag_outcome(
+                        hwnd,
+                        drop_result,
+                        out_dropeffect,
+                        &drag_state,
+                        started_at,
+                    )?);
                 }
             }
         }
-        Ok(())
+        Ok(crate::DragSession { inner: DragSession })
     } else {
         Err(crate::Error::UnsupportedWindowHandle)
     }
 }
 
-fn get_drag_image(image: Image) -> Option<SHDRAGIMAGE> {
-    let hbitmap = match image {
-        Image::Raw(bytes) => image::read_bytes_to_hbitmap(&bytes).ok(),
-        Image::File(path) => image::read_path_to_hbitmap(&path).ok(),
+fn get_drag_outcome(
+    hwnd: HWND,
+    drop_result: HRESULT,
+    effect: DROPEFFECT,
+    drag_state: &DragState,
+    started_at: Instant,
+) -> Result<DragOutcome> {
+    let mut pt = POINT { x: 0, y: 0 };
+    let cursor_position = unsafe { GetCursorPos(&mut pt) }
+        .ok()
+        .map(|_| CursorPosition { x: pt.x, y: pt.y });
+    let destination = cursor_position.as_ref().map(|_| drop_destination(pt));
+    let drop_location = cursor_position.as_ref().map(|_| drop_location(hwnd, pt));
+
+    let result = if drop_result == DRAGDROP_S_DROP {
+        if effect == DROPEFFECT_NONE {
+            DragResult::Cancel(CancelReason::NoTarget)
+        } else {
+            DragResult::Dropped(drag_operation(effect))
+        }
+    } else if drop_result == DRAGDROP_S_CANCEL {
+        if drag_state.escape_pressed.get() {
+            DragResult::Cancel(CancelReason::UserCancelled)
+        } else {
+            DragResult::Cancel(CancelReason::NoTarget)
+        }
+    } else {
+        DragResult::Cancel(CancelReason::Error)
     };
-    hbitmap.map(|hbitmap| unsafe {
+
+    Ok(DragOutcome {
+        result,
+        cursor_position,
+        modifiers: drag_state.modifiers(),
+        duration: started_at.elapsed(),
+        promised_file_path: None,
+        destination,
+        drop_location,
+    })
+}
+
+// the process is expected to be per-monitor DPI aware, so the cursor position is in physical pixels
+fn drop_location(hwnd: HWND, point: POINT) -> DropLocation {
+    unsafe {
+        let mut window_position = point;
+        let window_position = ScreenToClient(hwnd, &mut window_position)
+            .as_bool()
+            .then_some(PhysicalPosition {
+                x: window_position.x,
+                y: window_position.y,
+            });
+        DropLocation::from_physical(
+            PhysicalPosition {
+                x: point.x,
+                y: point.y,
+            },
+            window_position,
+            monitor_info(MonitorFromPoint(point, MONITOR_DEFAULTTONULL)),
+        )
+    }
+}
+
+unsafe fn monitor_info(monitor: HMONITOR) -> Option<MonitorInfo> {
+    if monitor.0 == 0 {
+        return None;
+    }
+    let mut info = MONITORINFOEXW::default();
+    info.monitorInfo.cbSize = std::mem::size_of::<MONITORINFOEXW>() as u32;
+    if !GetMonitorInfoW(
+        monitor,
+        &mut info as *mut MONITORINFOEXW as *mut MONITORINFO,
+    )
+    .as_bool()
+    {
+        return None;
+    }
+    let (mut dpi_x, mut dpi_y) = (0, 0);
+    let scale_factor = match GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y) {
+        Ok(()) => f64::from(dpi_x) / 96.0,
+        Err(_) => 1.0,
+    };
+    let rect = info.monitorInfo.rcMonitor;
+    let name_len = info
+        .szDevice
+        .iter()
+        .position(|c| *c == 0)
+        .unwrap_or(info.szDevice.len());
+    Some(MonitorInfo {
+        name: Some(String::from_utf16_lossy(&info.szDevice[..name_len])),
+        position: PhysicalPosition {
+            x: rect.left,
+            y: rect.top,
+        },
+        size: PhysicalSize {
+            width: (rect.right - rect.left) as u32,
+            height: (rect.bottom - rect.top) as u32,
+        },
+        scale_factor,
+    })
+}
+
+fn drop_destination(point: POINT) -> DropDestination {
+    unsafe {
+        let window = GetAncestor(WindowFromPoint(point), GA_ROOT);
+        if window.0 == 0 || window == GetShellWindow() || is_desktop_window(window) {
+            return DropDestination::Desktop;
+        }
+
+        let mut process_id = 0;
+        GetWindowThreadProcessId(window, Some(&mut process_id as *mut u32));
+        let window_id = Some(window.0 as u64);
+        if process_id == GetCurrentProcessId() {
+            DropDestination::OwnWindow { window_id }
+        } else {
+            DropDestination::Foreign {
+                window_id,
+                app_class: Some(class_name(window)).filter(|class| !class.is_empty()),
+                pid: (process_id != 0).then_some(process_id),
+            }
+        }
+    }
+}
+
+// the desktop icons live in a `WorkerW` window on top of the `Progman` shell window
+unsafe fn is_desktop_window(window: HWND) -> bool {
+    let class_name = class_name(window);
+    class_name == "WorkerW" || class_name == "Progman"
+}
+
+unsafe fn class_name(window: HWND) -> String {
+    // window class names are at most 256 characters long
+    let mut class_name = [0u16; 256];
+    let len = GetClassNameW(window, &mut class_name) as usize;
+    String::from_utf16_lossy(&class_name[..len])
+}
+
+fn drop_effects(operations: &[DragOperation]) -> DROPEFFECT {
+    let effects = operations
+        .iter()
+        .fold(DROPEFFECT_NONE, |effects, operation| {
+            effects
+                | match operation {
+                    DragOperation::Copy => DROPEFFECT_COPY,
+                    DragOperation::Move => DROPEFFECT_MOVE,
+                    DragOperation::Link => DROPEFFECT_LINK,
+                    DragOperation::Ask => DROPEFFECT_NONE,
+                }
+        });
+    // copy is offered when no operation is allowed, matching the default options
+    if effects == DROPEFFECT_NONE {
+        DROPEFFECT_COPY
+    } else {
+        effects
+    }
+}
+
+fn drag_operation(effect: DROPEFFECT) -> DragOperation {
+    if effect & DROPEFFECT_MOVE != DROPEFFECT_NONE {
+        DragOperation::Move
+    } else if effect & DROPEFFECT_LINK != DROPEFFECT_NONE {
+        DragOperation::Link
+    } else {
+        DragOperation::Copy
+    }
+}
+
+fn get_drag_image(image: Image, anchor: Option<ImageAnchor>) -> crate::Result<SHDRAGIMAGE> {
+    let hbitmap = match image {
+        Image::Raw(bytes) => image::read_bytes_to_hbitmap(&bytes),
+        Image::File(path) => image::read_path_to_hbitmap(&path),
+        Image::Rgba {
+            width,
+            height,
+            pixels,
+        } => image::rgba_to_hbitmap(width, height, &pixels),
+    }
+    .map_err(|_| crate::Error::InvalidImage)?;
+    Ok(unsafe {
         // get image size
         let mut bitmap: BITMAP = BITMAP::default();
         let (width, height) = if 0
@@ -327,13 +635,19 @@ fn get_drag_image(image: Image) -> Option<SHDRAGIMAGE> {
         } else {
             (bitmap.bmWidth, bitmap.bmHeight)
         };
+        let (hot_x, hot_y) = anchor.map_or((0., 0.), |anchor| {
+            anchor.offset(f64::from(width), f64::from(height))
+        });
 
         SHDRAGIMAGE {
             sizeDragImage: SIZE {
                 cx: width,
                 cy: height,
             },
-            ptOffset: POINT { x: 0, y: 0 },
+            ptOffset: POINT {
+                x: hot_x.round() as i32,
+                y: hot_y.round() as i32,
+            },
             hbmpDragImage: hbitmap,
             crColorKey: COLORREF(0x00000000),
         }
@@ -341,7 +655,7 @@ fn get_drag_image(image: Image) -> Option<SHDRAGIMAGE> {
 }
 
 fn get_hglobal(size: usize, buffer: Vec<u16>) -> Result<HGLOBAL> {
-    let handle = unsafe { GlobalAlloc(GMEM_FIXED, size).unwrap() };
+    let handle = unsafe { GlobalAlloc(GMEM_FIXED, size) }?;
     let ptr = unsafe { GlobalLock(handle) };
 
     let header = ptr as *mut DROPFILES;
@@ -362,26 +676,60 @@ pub fn create_instance<T: Interface + ComInterface>(clsid: &GUID) -> Result<T> {
     unsafe { CoCreateInstance(clsid, None, CLSCTX_ALL) }
 }
 
-fn get_file_data_object(paths: &[PathBuf]) -> Option<IDataObject> {
+fn get_file_data_object(paths: &[PathBuf]) -> crate::Result<IDataObject> {
     unsafe {
-        let shell_item_array = get_shell_item_array(paths).unwrap();
-        shell_item_array.BindToHandler(None, &BHID_DataObject).ok()
+        let shell_item_array = get_shell_item_array(paths)?;
+        Ok(shell_item_array.BindToHandler(None, &BHID_DataObject)?)
     }
 }
 
-fn get_shell_item_array(paths: &[PathBuf]) -> Option<IShellItemArray> {
+fn get_shell_item_array(paths: &[PathBuf]) -> crate::Result<IShellItemArray> {
     unsafe {
-        let list: Vec<*const Common::ITEMIDLIST> = paths
+        let list = paths
             .iter()
-            .map(|path| get_file_item_id(path).cast_const())
-            .collect();
-        SHCreateShellItemArrayFromIDLists(&list).ok()
+            .map(|path| get_file_item_id(path).map(|id| id.cast_const()))
+            .collect::<crate::Result<Vec<*const Common::ITEMIDLIST>>>()?;
+        Ok(SHCreateShellItemArrayFromIDLists(&list)?)
     }
 }
 
-fn get_file_item_id(path: &Path) -> *mut Common::ITEMIDLIST {
-    unsafe {
+fn get_file_item_id(path: &Path) -> crate::Result<*mut Common::ITEMIDLIST> {
+    let id = unsafe {
         let wide_path: Vec<u16> = path.as_os_str().encode_wide().chain(once(0)).collect();
         windows::Win32::UI::Shell::ILCreateFromPathW(PCWSTR::from_raw(wide_path.as_ptr()))
+    };
+    if id.is_null() {
+        Err(crate::Error::FileNotFound(path.to_path_buf()))
+    } else {
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_file_is_an_error() {
+        init_ole();
+        let paths = vec![PathBuf::from("C:\\drag-rs\\does\\not\\exist.txt")];
+        assert!(matches!(
+            get_shell_item_array(&paths),
+            Err(crate::Error::FileNotFound(_))
+        ));
+        assert!(matches!(
+            get_file_data_object(&paths),
+            Err(crate::Error::FileNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn ask_only_falls_back_to_copy() {
+        assert_eq!(drop_effects(&[DragOperation::Ask]), DROPEFFECT_COPY);
+        assert_eq!(drop_effects(&[]), DROPEFFECT_COPY);
+        assert_eq!(
+            drop_effects(&[DragOperation::Move, DragOperation::Ask]),
+            DROPEFFECT_MOVE
+        );
     }
 }
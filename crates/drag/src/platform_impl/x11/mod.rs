@@ -0,0 +1,988 @@
+// Copyright 2023-2023 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+use std::{
+    ffi::CStr,
+    thread,
+    time::{Duration, Instant},
+};
+
+use gtk::gdk_pixbuf::Pixbuf;
+use raw_window_handle::RawDisplayHandle;
+use x11rb::{
+    atom_manager,
+    connection::{Connection, RequestConnection},
+    errors::ReplyOrIdError,
+    protocol::{
+        randr::ConnectionExt as _,
+        shape::{self, ConnectionExt as _, SK, SO},
+        xproto::{
+            Atom, AtomEnum, ClientMessageEvent, ClipOrdering, ColormapAlloc, ConfigureWindowAux,
+            ConnectionExt as _, CreateGCAux, CreateWindowAux, EventMask, ImageFormat, ImageOrder,
+            KeyButMask, PropMode, SelectionNotifyEvent, SelectionRequestEvent, VisualClass, Window,
+            WindowClass, SELECTION_NOTIFY_EVENT,
+        },
+        Event,
+    },
+    rust_connection::RustConnection,
+    wrapper::ConnectionExt as _,
+    xcb_ffi::XCBConnection,
+    COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME, NONE,
+};
+
+use super::{
+    mime::MimeOffer,
+    platform::{image_to_pixbuf, premultiplied_argb, DragSession},
+};
+use crate::{
+    CancelReason, CursorPosition, DragEvent, DragItem, DragOperation, DragOutcome, DragResult,
+    DropDestination, DropLocation, Error, Image, ImageAnchor, Modifiers, MonitorInfo, Options,
+    PhysicalPosition, PhysicalSize,
+};
+
+const XDND_VERSION: u32 = 5;
+// the oldest version that sends the action in XdndPosition
+const XDND_MIN_VERSION: u32 = 3;
+// the pointer is polled since the implicit grab still belongs to the window the drag started on
+const POLL_INTERVAL: Duration = Duration::from_millis(16);
+// how long the target may take to answer the last position before the drop
+const STATUS_TIMEOUT: Duration = Duration::from_secs(1);
+// how long the target may take to read the data after the drop
+const FINISHED_TIMEOUT: Duration = Duration::from_secs(5);
+const XK_ESCAPE: u32 = 0xff1b;
+
+atom_manager! {
+    Atoms: AtomsCookie {
+        XdndAware,
+        XdndEnter,
+        XdndPosition,
+        XdndStatus,
+        XdndLeave,
+        XdndDrop,
+        XdndFinished,
+        XdndSelection,
+        XdndTypeList,
+        XdndActionList,
+        XdndActionCopy,
+        XdndActionMove,
+        XdndActionLink,
+        XdndActionAsk,
+        TARGETS,
+        _NET_WM_WINDOW_TYPE,
+        _NET_WM_WINDOW_TYPE_DND,
+        _NET_WM_WINDOW_TYPE_DESKTOP,
+        _NET_WM_PID,
+        WM_STATE,
+    }
+}
+
+/// Starts a blocking XDND drag out of an X11 window that is not managed by GTK.
+///
+/// The drag runs on its own connection to the display of the window, and the calling thread
+/// does not return to its event loop until the drag ends.
+pub fn start_drag<F: Fn(DragOutcome) + Send + 'static>(
+    display: RawDisplayHandle,
+    window: Window,
+    item: DragItem,
+    image: Image,
+    on_drop_callback: F,
+    mut options: Options,
+) -> crate::Result<crate::DragSession> {
+    crate::validate::validate(&item, &image)?;
+    // released when the blocking drag returned
+    let _payload = crate::local::attach(options.payload.take());
+    let icon = image_to_pixbuf(&image).ok_or(Error::InvalidImage)?;
+    let offer = MimeOffer::new(item)?;
+
+    let started_at = Instant::now();
+    let conn = connect(display)?;
+    let mut source = XdndSource::new(
+        conn,
+        window,
+        offer,
+        &options.allowed_operations,
+        options.on_event.take().map(crate::throttle_events),
+    )?;
+    source.show_icon(&icon, options.image_anchor)?;
+    let outcome = source.run(started_at)?;
+
+    if let DragResult::Dropped(DragOperation::Move) = outcome.result {
+        if let Some(on_move_completed) = &options.on_move_completed {
+            on_move_completed();
+        }
+    }
+    on_drop_callback(outcome);
+
+    Ok(crate::DragSession {
+        inner: DragSession::Native,
+    })
+}
+
+/// Opens a new connection to the X server of the given display.
+fn connect(display: RawDisplayHandle) -> crate::Result<RustConnection> {
+    let name = match display {
+        RawDisplayHandle::Xlib(handle) => handle.display.map(|display| {
+            // Safety: the display handle points to an open Xlib display
+            unsafe { CStr::from_ptr(::x11::xlib::XDisplayString(display.as_ptr().cast())) }
+                .to_string_lossy()
+                .into_owned()
+        }),
+        // XCB does not expose the name a connection was opened with, the default display is used
+        RawDisplayHandle::Xcb(_) => None,
+        _ => return Err(Error::UnsupportedWindowHandle),
+    };
+    let (conn, _) = x11rb::connect(name.as_deref()).map_err(|_| Error::FailedToStartDrag)?;
+    Ok(conn)
+}
+
+/// The XDND target under the pointer.
+struct Target {
+    window: Window,
+    version: u32,
+    accepted: bool,
+    action: Atom,
+    awaiting_status: bool,
+    // XdndPosition must not be sent again before the target answers the previous one
+    pending_position: Option<((i16, i16), KeyButMask)>,
+}
+
+struct XdndSource {
+    conn: RustConnection,
+    atoms: Atoms,
+    root: Window,
+    // the window the drag started on
+    origin: Window,
+    // owns the XdndSelection and is the source window of every XDND message
+    window: Window,
+    icon: Option<Window>,
+    // the point of the icon held under the pointer
+    icon_hotspot: (i16, i16),
+    offer: MimeOffer,
+    types: Vec<(Atom, String)>,
+    actions: Vec<Atom>,
+    escape_keycode: Option<u8>,
+    on_event: Option<Box<dyn Fn(DragEvent) + Send>>,
+}
+
+impl XdndSource {
+    fn new(
+        conn: RustConnection,
+        window: Window,
+        offer: MimeOffer,
+        operations: &[DragOperation],
+        on_event: Option<Box<dyn Fn(DragEvent) + Send>>,
+    ) -> Result<Self, ReplyOrIdError> {
+        let atoms = Atoms::new(&conn)?.reply()?;
+        let root = conn.get_geometry(window)?.reply()?.root;
+
+        let source = conn.generate_id()?;
+        conn.create_window(
+            COPY_DEPTH_FROM_PARENT,
+            source,
+            root,
+            -1,
+            -1,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_ONLY,
+            COPY_FROM_PARENT,
+            &CreateWindowAux::new().override_redirect(1),
+        )?;
+
+        let cookies = offer
+            .types()
+            .into_iter()
+            .map(|mime| Ok((conn.intern_atom(false, mime.as_bytes())?, mime)))
+            .collect::<Result<Vec<_>, ReplyOrIdError>>()?;
+        let types = cookies
+            .into_iter()
+            .map(|(cookie, mime)| Ok((cookie.reply()?.atom, mime)))
+            .collect::<Result<Vec<_>, ReplyOrIdError>>()?;
+        let type_atoms: Vec<Atom> = types.iter().map(|(atom, _)| *atom).collect();
+        conn.change_property32(
+            PropMode::REPLACE,
+            source,
+            atoms.XdndTypeList,
+            AtomEnum::ATOM,
+            &type_atoms,
+        )?;
+
+        let actions = xdnd_actions(&atoms, operations);
+        conn.change_property32(
+            PropMode::REPLACE,
+            source,
+            atoms.XdndActionList,
+            AtomEnum::ATOM,
+            &actions,
+        )?;
+        conn.set_selection_owner(source, atoms.XdndSelection, CURRENT_TIME)?;
+
+        let escape_keycode = escape_keycode(&conn)?;
+
+        Ok(Self {
+            conn,
+            atoms,
+            root,
+            origin: window,
+            window: source,
+            icon: None,
+            icon_hotspot: (0, 0),
+            offer,
+            types,
+            actions,
+            escape_keycode,
+            on_event,
+        })
+    }
+
+    // the icon needs an ARGB visual for transparency and an empty input shape,
+    // otherwise it would be found as the window under the pointer; it is skipped when either is missing
+    fn show_icon(
+        &mut self,
+        pixbuf: &Pixbuf,
+        anchor: Option<ImageAnchor>,
+    ) -> Result<(), ReplyOrIdError> {
+        let Some(screen) = self.conn.setup().roots.iter().find(|s| s.root == self.root) else {
+            return Ok(());
+        };
+        let visual = screen
+            .allowed_depths
+            .iter()
+            .filter(|depth| depth.depth == 32)
+            .flat_map(|depth| &depth.visuals)
+            .find(|visual| visual.class == VisualClass::TRUE_COLOR)
+            .map(|visual| visual.visual_id);
+        let has_shape = self
+            .conn
+            .extension_information(shape::X11_EXTENSION_NAME)?
+            .is_some();
+        let (Some(visual), true) = (visual, has_shape) else {
+            return Ok(());
+        };
+        let (Ok(width), Ok(height)) = (
+            u16::try_from(pixbuf.width()),
+            u16::try_from(pixbuf.height()),
+        ) else {
+            return Ok(());
+        };
+        let lsb_first = self.conn.setup().image_byte_order == ImageOrder::LSB_FIRST;
+        let pixels = premultiplied_argb(pixbuf, lsb_first);
+        if pixels.len() + 24 > self.conn.maximum_request_bytes() {
+            return Ok(());
+        }
+
+        let pixmap = self.conn.generate_id()?;
+        self.conn
+            .create_pixmap(32, pixmap, self.root, width, height)?;
+        let gc = self.conn.generate_id()?;
+        self.conn.create_gc(gc, pixmap, &CreateGCAux::new())?;
+        self.conn.put_image(
+            ImageFormat::Z_PIXMAP,
+            pixmap,
+            gc,
+            width,
+            height,
+            0,
+            0,
+            0,
+            32,
+            &pixels,
+        )?;
+
+        let (hot_x, hot_y) = anchor.map_or((0., 0.), |anchor| {
+            anchor.offset(f64::from(width), f64::from(height))
+        });
+        self.icon_hotspot = (hot_x.round() as i16, hot_y.round() as i16);
+
+        let colormap = self.conn.generate_id()?;
+        self.conn
+            .create_colormap(ColormapAlloc::NONE, colormap, self.root, visual)?;
+        let icon = self.conn.generate_id()?;
+        let pointer = self.conn.query_pointer(self.root)?.reply()?;
+        self.conn.create_window(
+            32,
+            icon,
+            self.root,
+            pointer.root_x.saturating_sub(self.icon_hotspot.0),
+            pointer.root_y.saturating_sub(self.icon_hotspot.1),
+            width,
+            height,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            visual,
+            &CreateWindowAux::new()
+                .override_redirect(1)
+                .colormap(colormap)
+                .border_pixel(0)
+                .background_pixmap(pixmap),
+        )?;
+        self.conn
+            .shape_rectangles(SO::SET, SK::INPUT, ClipOrdering::UNSORTED, icon, 0, 0, &[])?;
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            icon,
+            self.atoms._NET_WM_WINDOW_TYPE,
+            AtomEnum::ATOM,
+            &[self.atoms._NET_WM_WINDOW_TYPE_DND],
+        )?;
+        self.conn.map_window(icon)?;
+        self.conn.flush()?;
+        self.icon = Some(icon);
+        Ok(())
+    }
+
+    fn run(&self, started_at: Instant) -> Result<DragOutcome, ReplyOrIdError> {
+        self.emit(DragEvent::Started);
+
+        let mut target = None;
+        let mut last_state = None;
+        loop {
+            self.handle_events(&mut target)?;
+
+            let pointer = self.conn.query_pointer(self.root)?.reply()?;
+            let position = (pointer.root_x, pointer.root_y);
+            let cursor_position = CursorPosition {
+                x: position.0.into(),
+                y: position.1.into(),
+            };
+            let modifiers = get_modifiers(pointer.mask);
+
+            let escape_pressed = self.escape_pressed()?;
+            if escape_pressed || !pointer.mask.contains(KeyButMask::BUTTON1) {
+                // the pointer may move while the target reads the data
+                let destination = destination(&self.conn, &self.atoms, self.root, position)?;
+                let drop_location = self.drop_location(position)?;
+                let result = if escape_pressed {
+                    self.leave(&mut target)?;
+                    DragResult::Cancel(CancelReason::UserCancelled)
+                } else {
+                    self.drop(target)?
+                };
+                return Ok(DragOutcome {
+                    result,
+                    cursor_position: Some(cursor_position),
+                    modifiers,
+                    duration: started_at.elapsed(),
+                    promised_file_path: None,
+                    destination: Some(destination),
+                    drop_location: Some(drop_location),
+                });
+            }
+
+            // modifier changes are forwarded too, since they select the suggested action
+            if last_state != Some((position, pointer.mask)) {
+                if last_state.map(|(last_position, _)| last_position) != Some(position) {
+                    self.emit(DragEvent::Moved(cursor_position));
+                    if let Some(icon) = self.icon {
+                        self.conn.configure_window(
+                            icon,
+                            &ConfigureWindowAux::new()
+                                .x(i32::from(position.0) - i32::from(self.icon_hotspot.0))
+                                .y(i32::from(position.1) - i32::from(self.icon_hotspot.1)),
+                        )?;
+                    }
+                }
+                last_state = Some((position, pointer.mask));
+                self.update_target(&mut target, position, pointer.mask)?;
+            }
+
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    fn update_target(
+        &self,
+        target: &mut Option<Target>,
+        position: (i16, i16),
+        mask: KeyButMask,
+    ) -> Result<(), ReplyOrIdError> {
+        let under = self.find_target(position)?;
+        if target.as_ref().map(|target| target.window) != under.map(|(window, _)| window) {
+            self.leave(target)?;
+            if let Some((window, version)) = under {
+                let mut data = [
+                    self.window,
+                    (version << 24) | u32::from(self.types.len() > 3),
+                    0,
+                    0,
+                    0,
+                ];
+                for (slot, (atom, _)) in data[2..].iter_mut().zip(&self.types) {
+                    *slot = *atom;
+                }
+                self.send(window, self.atoms.XdndEnter, data)?;
+                *target = Some(Target {
+                    window,
+                    version,
+                    accepted: false,
+                    action: NONE,
+                    awaiting_status: false,
+                    pending_position: None,
+                });
+                self.emit(DragEvent::Entered);
+            }
+        }
+
+        if let Some(target) = target {
+            self.send_position(target, position, mask)?;
+        }
+        Ok(())
+    }
+
+    fn send_position(
+        &self,
+        target: &mut Target,
+        (x, y): (i16, i16),
+        mask: KeyButMask,
+    ) -> Result<(), ReplyOrIdError> {
+        if target.awaiting_status {
+            target.pending_position = Some(((x, y), mask));
+            return Ok(());
+        }
+        let coordinates = (u32::from(x as u16) << 16) | u32::from(y as u16);
+        let action = self.suggested_action(mask);
+        self.send(
+            target.window,
+            self.atoms.XdndPosition,
+            [self.window, 0, coordinates, CURRENT_TIME, action],
+        )?;
+        target.awaiting_status = true;
+        Ok(())
+    }
+
+    fn leave(&self, target: &mut Option<Target>) -> Result<(), ReplyOrIdError> {
+        if let Some(target) = target.take() {
+            self.send(
+                target.window,
+                self.atoms.XdndLeave,
+                [self.window, 0, 0, 0, 0],
+            )?;
+            if target.accepted {
+                self.emit(DragEvent::ActionChanged(None));
+            }
+            self.emit(DragEvent::Left);
+        }
+        Ok(())
+    }
+
+    fn drop(&self, mut target: Option<Target>) -> Result<DragResult, ReplyOrIdError> {
+        // the target decides whether it accepts the drop when answering the last position
+        if let Some(target) = &mut target {
+            target.pending_position = None;
+        }
+        let deadline = Instant::now() + STATUS_TIMEOUT;
+        while target.as_ref().is_some_and(|target| target.awaiting_status)
+            && Instant::now() < deadline
+        {
+            thread::sleep(POLL_INTERVAL);
+            self.handle_events(&mut target)?;
+        }
+
+        let window = match &target {
+            Some(target) if target.accepted => target.window,
+            _ => {
+                self.leave(&mut target)?;
+                return Ok(DragResult::Cancel(CancelReason::NoTarget));
+            }
+        };
+        self.send(
+            window,
+            self.atoms.XdndDrop,
+            [self.window, 0, CURRENT_TIME, 0, 0],
+        )?;
+
+        // keep serving the selection until the target is done reading it
+        let deadline = Instant::now() + FINISHED_TIMEOUT;
+        while Instant::now() < deadline {
+            if let Some((accepted, action)) = self.handle_events(&mut target)? {
+                return Ok(if accepted {
+                    DragResult::Dropped(self.operation(action))
+                } else {
+                    DragResult::Cancel(CancelReason::Error)
+                });
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        Ok(DragResult::Cancel(CancelReason::TimeoutExpired))
+    }
+
+    /// Handles the pending events, returning whether the target accepted the drop and its action once it finished.
+    fn handle_events(
+        &self,
+        target: &mut Option<Target>,
+    ) -> Result<Option<(bool, Atom)>, ReplyOrIdError> {
+        let mut finished = None;
+        while let Some(event) = self.conn.poll_for_event()? {
+            match event {
+                Event::ClientMessage(event) => {
+                    let data = event.data.as_data32();
+                    let Some(target) = target.as_mut().filter(|target| target.window == data[0])
+                    else {
+                        continue;
+                    };
+                    if event.type_ == self.atoms.XdndStatus {
+                        let accepted = data[1] & 1 == 1;
+                        let action = if accepted { data[4] } else { NONE };
+                        if (accepted, action) != (target.accepted, target.action) {
+                            self.emit(DragEvent::ActionChanged(
+                                accepted.then(|| self.operation(action)),
+                            ));
+                        }
+                        target.accepted = accepted;
+                        target.action = action;
+                        target.awaiting_status = false;
+                        if let Some((position, mask)) = target.pending_position.take() {
+                            self.send_position(target, position, mask)?;
+                        }
+                    } else if event.type_ == self.atoms.XdndFinished {
+                        // only version 5 reports the outcome, older targets finish successful drops
+                        finished = Some(if target.version >= 5 {
+                            (data[1] & 1 == 1, data[2])
+                        } else {
+                            (true, target.action)
+                        });
+                    }
+                }
+                Event::SelectionRequest(request) => self.send_selection(request)?,
+                _ => {}
+            }
+        }
+        Ok(finished)
+    }
+
+    fn send_selection(&self, request: SelectionRequestEvent) -> Result<(), ReplyOrIdError> {
+        // obsolete clients do not set a property and expect the target to be used instead
+        let property = if request.property == NONE {
+            request.target
+        } else {
+            request.property
+        };
+
+        let written = if request.selection != self.atoms.XdndSelection {
+            false
+        } else if request.target == self.atoms.TARGETS {
+            let atoms: Vec<Atom> = self.types.iter().map(|(atom, _)| *atom).collect();
+            self.conn.change_property32(
+                PropMode::REPLACE,
+                request.requestor,
+                property,
+                AtomEnum::ATOM,
+                &atoms,
+            )?;
+            true
+        } else if let Some(data) = self
+            .types
+            .iter()
+            .find(|(atom, _)| *atom == request.target)
+            .and_then(|(_, mime)| self.offer.data(mime))
+            // INCR transfers are not supported
+            .filter(|data| data.len() + 24 <= self.conn.maximum_request_bytes())
+        {
+            self.conn.change_property8(
+                PropMode::REPLACE,
+                request.requestor,
+                property,
+                request.target,
+                &data,
+            )?;
+            true
+        } else {
+            false
+        };
+
+        let notify = SelectionNotifyEvent {
+            response_type: SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: request.time,
+            requestor: request.requestor,
+            selection: request.selection,
+            target: request.target,
+            property: if written { property } else { NONE },
+        };
+        self.conn
+            .send_event(false, request.requestor, EventMask::NO_EVENT, notify)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    // walks down the window tree from the root until an XdndAware window is found
+    fn find_target(&self, (x, y): (i16, i16)) -> Result<Option<(Window, u32)>, ReplyOrIdError> {
+        let mut window = self.root;
+        loop {
+            let child = self
+                .conn
+                .translate_coordinates(self.root, window, x, y)?
+                .reply()?
+                .child;
+            if child == NONE {
+                return Ok(None);
+            }
+            if let Some(version) = self.xdnd_version(child)? {
+                return Ok(Some((child, version)));
+            }
+            window = child;
+        }
+    }
+
+    fn drop_location(&self, (x, y): (i16, i16)) -> Result<DropLocation, ReplyOrIdError> {
+        let origin = self
+            .conn
+            .translate_coordinates(self.root, self.origin, x, y)?
+            .reply()?;
+        let scale_factor = scale_factor(&self.conn, self.root)?;
+        // RandR may be missing, in which case the monitor is unknown
+        let monitor = self
+            .conn
+            .randr_get_monitors(self.root, true)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .and_then(|reply| {
+                reply.monitors.into_iter().find(|monitor| {
+                    (monitor.x..monitor.x.saturating_add_unsigned(monitor.width)).contains(&x)
+                        && (monitor.y..monitor.y.saturating_add_unsigned(monitor.height))
+                            .contains(&y)
+                })
+            });
+        let monitor = match monitor {
+            Some(monitor) => {
+                let name = self.conn.get_atom_name(monitor.name)?.reply()?.name;
+                Some(MonitorInfo {
+                    name: Some(String::from_utf8_lossy(&name).into_owned()),
+                    position: PhysicalPosition {
+                        x: monitor.x.into(),
+                        y: monitor.y.into(),
+                    },
+                    size: PhysicalSize {
+                        width: monitor.width.into(),
+                        height: monitor.height.into(),
+                    },
+                    scale_factor,
+                })
+            }
+            None => None,
+        };
+
+        Ok(DropLocation::from_physical(
+            PhysicalPosition {
+                x: x.into(),
+                y: y.into(),
+            },
+            Some(PhysicalPosition {
+                x: origin.dst_x.into(),
+                y: origin.dst_y.into(),
+            }),
+            monitor,
+        ))
+    }
+
+    fn xdnd_version(&self, window: Window) -> Result<Option<u32>, ReplyOrIdError> {
+        let reply = self
+            .conn
+            .get_property(false, window, self.atoms.XdndAware, AtomEnum::ATOM, 0, 1)?
+            .reply()?;
+        Ok(reply
+            .value32()
+            .and_then(|mut value| value.next())
+            .filter(|version| *version >= XDND_MIN_VERSION)
+            .map(|version| version.min(XDND_VERSION)))
+    }
+
+    fn escape_pressed(&self) -> Result<bool, ReplyOrIdError> {
+        let Some(keycode) = self.escape_keycode else {
+            return Ok(false);
+        };
+        let keys = self.conn.query_keymap()?.reply()?.keys;
+        Ok(keys[usize::from(keycode / 8)] & (1 << (keycode % 8)) != 0)
+    }
+
+    // follows GTK: Shift moves, Control copies and both link
+    fn suggested_action(&self, mask: KeyButMask) -> Atom {
+        let preferred = match (
+            mask.contains(KeyButMask::SHIFT),
+            mask.contains(KeyButMask::CONTROL),
+        ) {
+            (true, true) => Some(self.atoms.XdndActionLink),
+            (true, false) => Some(self.atoms.XdndActionMove),
+            (false, true) => Some(self.atoms.XdndActionCopy),
+            (false, false) => None,
+        };
+        preferred
+            .filter(|action| self.actions.contains(action))
+            .unwrap_or(self.actions[0])
+    }
+
+    fn operation(&self, action: Atom) -> DragOperation {
+        if action == self.atoms.XdndActionMove {
+            DragOperation::Move
+        } else if action == self.atoms.XdndActionLink {
+            DragOperation::Link
+        } else if action == self.atoms.XdndActionAsk {
+            DragOperation::Ask
+        } else {
+            DragOperation::Copy
+        }
+    }
+
+    fn send(
+        &self,
+        window: Window,
+        message_type: Atom,
+        data: [u32; 5],
+    ) -> Result<(), ReplyOrIdError> {
+        let event = ClientMessageEvent::new(32, window, message_type, data);
+        self.conn
+            .send_event(false, window, EventMask::NO_EVENT, event)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn emit(&self, event: DragEvent) {
+        if let Some(on_event) = &self.on_event {
+            on_event(event);
+        }
+    }
+}
+
+/// Classifies the window under the pointer, for the GTK backend whose drag context only knows XDND targets.
+///
+/// # Safety
+///
+/// `display` must be an open Xlib display, used on the thread owning it.
+pub(crate) unsafe fn destination_under_pointer(
+    display: *mut ::x11::xlib::Display,
+) -> Option<DropDestination> {
+    // the requests go through the XCB connection of the display instead of a connection of their own
+    let xcb = ::x11::xlib_xcb::XGetXCBConnection(display);
+    let conn = XCBConnection::from_raw_xcb_connection(xcb, false).ok()?;
+    let screen = ::x11::xlib::XDefaultScreen(display) as usize;
+    let atoms = Atoms::new(&conn).ok()?.reply().ok()?;
+    let root = conn.setup().roots.get(screen)?.root;
+    let pointer = conn.query_pointer(root).ok()?.reply().ok()?;
+    destination(&conn, &atoms, root, (pointer.root_x, pointer.root_y)).ok()
+}
+
+fn destination(
+    conn: &impl Connection,
+    atoms: &Atoms,
+    root: Window,
+    position: (i16, i16),
+) -> Result<DropDestination, ReplyOrIdError> {
+    let Some(window) = client_window(conn, atoms, root, position)? else {
+        return Ok(DropDestination::Desktop);
+    };
+    let window_types = conn
+        .get_property(
+            false,
+            window,
+            atoms._NET_WM_WINDOW_TYPE,
+            AtomEnum::ATOM,
+            0,
+            32,
+        )?
+        .reply()?;
+    if window_types
+        .value32()
+        .is_some_and(|mut types| types.any(|atom| atom == atoms._NET_WM_WINDOW_TYPE_DESKTOP))
+    {
+        return Ok(DropDestination::Desktop);
+    }
+
+    // the pid is set by the client itself, which is assumed to run on this host
+    let pid = conn
+        .get_property(false, window, atoms._NET_WM_PID, AtomEnum::CARDINAL, 0, 1)?
+        .reply()?
+        .value32()
+        .and_then(|mut value| value.next());
+    let window_id = Some(u64::from(window));
+    if pid == Some(std::process::id()) {
+        return Ok(DropDestination::OwnWindow { window_id });
+    }
+
+    // WM_CLASS holds the instance name followed by the class name, both null terminated
+    let wm_class = conn
+        .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 256)?
+        .reply()?
+        .value;
+    let app_class = wm_class
+        .split(|byte| *byte == 0)
+        .nth(1)
+        .filter(|class| !class.is_empty())
+        .map(|class| String::from_utf8_lossy(class).into_owned());
+    Ok(DropDestination::Foreign {
+        window_id,
+        app_class,
+        pid,
+    })
+}
+
+// walks down the window tree from the root until the client window holding WM_STATE is found,
+// falling back to the toplevel window when there is no window manager
+fn client_window(
+    conn: &impl Connection,
+    atoms: &Atoms,
+    root: Window,
+    (x, y): (i16, i16),
+) -> Result<Option<Window>, ReplyOrIdError> {
+    let mut window = root;
+    let mut toplevel = None;
+    loop {
+        let child = conn
+            .translate_coordinates(root, window, x, y)?
+            .reply()?
+            .child;
+        if child == NONE {
+            return Ok(toplevel);
+        }
+        toplevel.get_or_insert(child);
+        let wm_state = conn
+            .get_property(false, child, atoms.WM_STATE, AtomEnum::ANY, 0, 0)?
+            .reply()?;
+        if wm_state.type_ != NONE {
+            return Ok(Some(child));
+        }
+        window = child;
+    }
+}
+
+// X11 has no scale factor, toolkits follow the `Xft.dpi` resource instead
+fn scale_factor(conn: &RustConnection, root: Window) -> Result<f64, ReplyOrIdError> {
+    let resources = conn
+        .get_property(
+            false,
+            root,
+            AtomEnum::RESOURCE_MANAGER,
+            AtomEnum::STRING,
+            0,
+            u32::MAX,
+        )?
+        .reply()?
+        .value;
+    let dpi = String::from_utf8_lossy(&resources)
+        .lines()
+        .find_map(|line| line.strip_prefix("Xft.dpi:"))
+        .and_then(|dpi| dpi.trim().parse::<f64>().ok())
+        .filter(|dpi| *dpi > 0.0);
+    Ok(dpi.map_or(1.0, |dpi| dpi / 96.0))
+}
+
+// copy is offered when no operation is allowed, matching the default options
+fn xdnd_actions(atoms: &Atoms, operations: &[DragOperation]) -> Vec<Atom> {
+    let mut actions = Vec::new();
+    for operation in operations {
+        let action = match operation {
+            DragOperation::Copy => atoms.XdndActionCopy,
+            DragOperation::Move => atoms.XdndActionMove,
+            DragOperation::Link => atoms.XdndActionLink,
+            DragOperation::Ask => atoms.XdndActionAsk,
+        };
+        if !actions.contains(&action) {
+            actions.push(action);
+        }
+    }
+    if actions.is_empty() {
+        actions.push(atoms.XdndActionCopy);
+    }
+    actions
+}
+
+fn escape_keycode(conn: &RustConnection) -> Result<Option<u8>, ReplyOrIdError> {
+    let (min_keycode, max_keycode) = (conn.setup().min_keycode, conn.setup().max_keycode);
+    let mapping = conn
+        .get_keyboard_mapping(min_keycode, max_keycode - min_keycode + 1)?
+        .reply()?;
+    let keysyms_per_keycode = usize::from(mapping.keysyms_per_keycode).max(1);
+    Ok(mapping
+        .keysyms
+        .chunks(keysyms_per_keycode)
+        .position(|keysyms| keysyms.contains(&XK_ESCAPE))
+        .map(|index| min_keycode + index as u8))
+}
+
+fn get_modifiers(mask: KeyButMask) -> Modifiers {
+    Modifiers {
+        shift: mask.contains(KeyButMask::SHIFT),
+        control: mask.contains(KeyButMask::CONTROL),
+        alt: mask.contains(KeyButMask::MOD1),
+        meta: mask.contains(KeyButMask::MOD4),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // run under Xvfb with `cargo test -- --ignored`
+    #[test]
+    #[ignore = "requires an X server"]
+    fn serves_the_selection_to_a_local_target() {
+        let (target_conn, screen) = x11rb::connect(None).unwrap();
+        let root = target_conn.setup().roots[screen].root;
+        let atoms = Atoms::new(&target_conn).unwrap().reply().unwrap();
+
+        let target = target_conn.generate_id().unwrap();
+        target_conn
+            .create_window(
+                COPY_DEPTH_FROM_PARENT,
+                target,
+                root,
+                10,
+                10,
+                100,
+                100,
+                0,
+                WindowClass::INPUT_OUTPUT,
+                COPY_FROM_PARENT,
+                &CreateWindowAux::new().override_redirect(1),
+            )
+            .unwrap();
+        target_conn
+            .change_property32(
+                PropMode::REPLACE,
+                target,
+                atoms.XdndAware,
+                AtomEnum::ATOM,
+                &[XDND_VERSION],
+            )
+            .unwrap();
+        target_conn.map_window(target).unwrap();
+        target_conn.sync().unwrap();
+
+        let (conn, _) = x11rb::connect(None).unwrap();
+        let offer = MimeOffer::new(DragItem::Text("hello".into())).unwrap();
+        let source = XdndSource::new(conn, target, offer, &[DragOperation::Copy], None).unwrap();
+        assert_eq!(
+            source.find_target((50, 50)).unwrap(),
+            Some((target, XDND_VERSION))
+        );
+
+        let utf8_string = target_conn
+            .intern_atom(false, b"UTF8_STRING")
+            .unwrap()
+            .reply()
+            .unwrap()
+            .atom;
+        target_conn
+            .convert_selection(
+                target,
+                atoms.XdndSelection,
+                utf8_string,
+                atoms.XdndSelection,
+                CURRENT_TIME,
+            )
+            .unwrap();
+        target_conn.sync().unwrap();
+        source.conn.sync().unwrap();
+        source.handle_events(&mut None).unwrap();
+        source.conn.sync().unwrap();
+
+        let data = target_conn
+            .get_property(false, target, atoms.XdndSelection, utf8_string, 0, 1024)
+            .unwrap()
+            .reply()
+            .unwrap()
+            .value;
+        assert_eq!(data, b"hello");
+    }
+}
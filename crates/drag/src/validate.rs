@@ -0,0 +1,207 @@
+// Copyright 2023-2023 CrabNebula Ltd.
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+use std::path::Path;
+
+use crate::{DragItem, Error, Image, Result};
+
+/// Checks the drag item and image before any platform call, so every backend reports the same [`Error`].
+///
+/// Decoding the image is left to the backends, which each use their platform's decoder
+/// before any other native call.
+pub(crate) fn validate(item: &DragItem, image: &Image) -> Result<()> {
+    match item {
+        DragItem::Files(paths) => {
+            for path in paths {
+                validate_file(path)?;
+            }
+        }
+        DragItem::Data { types, .. } => {
+            if types.is_empty() {
+                return Err(Error::EmptyTypeList);
+            }
+        }
+        DragItem::Mixed {
+            files, data, types, ..
+        } => {
+            if files.is_empty() && data.is_empty() && types.is_empty() {
+                return Err(Error::EmptyTypeList);
+            }
+            for path in files {
+                validate_file(path)?;
+            }
+        }
+        // file promises are not implemented on macOS yet
+        #[cfg(target_os = "macos")]
+        DragItem::PromisedFile { .. } => return Err(Error::UnsupportedOperation),
+        #[cfg(not(target_os = "macos"))]
+        DragItem::PromisedFile { .. } => {}
+        DragItem::Text(_) | DragItem::Html { .. } | DragItem::Url { .. } => {}
+    }
+
+    match image {
+        Image::File(path) => {
+            if !path.exists() {
+                return Err(Error::ImageNotFound);
+            }
+        }
+        Image::Rgba {
+            width,
+            height,
+            pixels,
+        } => {
+            let len = u64::from(*width) * u64::from(*height) * 4;
+            if len == 0 || pixels.len() as u64 != len {
+                return Err(Error::InvalidImage);
+            }
+        }
+        Image::Raw(_) => {}
+    }
+
+    Ok(())
+}
+
+fn validate_file(path: &Path) -> Result<()> {
+    if !path.is_absolute() {
+        return Err(Error::RelativePath(path.to_path_buf()));
+    }
+    let metadata = match std::fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(Error::FileNotFound(path.to_path_buf()))
+        }
+        Err(_) => return Err(Error::UnreadableFile(path.to_path_buf())),
+    };
+    let readable = if metadata.is_dir() {
+        std::fs::read_dir(path).is_ok()
+    } else {
+        std::fs::File::open(path).is_ok()
+    };
+    if readable {
+        Ok(())
+    } else {
+        Err(Error::UnreadableFile(path.to_path_buf()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn image() -> Image {
+        Image::Raw(Vec::new())
+    }
+
+    fn existing_file(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("drag-{}-{name}", std::process::id()));
+        std::fs::write(&path, b"drag").unwrap();
+        path
+    }
+
+    fn missing_file() -> PathBuf {
+        std::env::temp_dir().join(format!("drag-{}-missing", std::process::id()))
+    }
+
+    #[test]
+    fn files_must_be_absolute() {
+        let item = DragItem::Files(vec![PathBuf::from("relative.txt")]);
+        assert!(matches!(
+            validate(&item, &image()),
+            Err(Error::RelativePath(path)) if path == Path::new("relative.txt")
+        ));
+    }
+
+    #[test]
+    fn files_must_exist() {
+        let item = DragItem::Files(vec![missing_file()]);
+        assert!(matches!(
+            validate(&item, &image()),
+            Err(Error::FileNotFound(path)) if path == missing_file()
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn files_must_be_readable() {
+        // a regular file used as a directory fails with ENOTDIR, even for root
+        let path = existing_file("not-a-dir").join("file.txt");
+        let item = DragItem::Files(vec![path.clone()]);
+        assert!(matches!(
+            validate(&item, &image()),
+            Err(Error::UnreadableFile(unreadable)) if unreadable == path
+        ));
+        std::fs::remove_file(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn data_needs_a_type() {
+        let item = DragItem::Data {
+            provider: Box::new(|_| None),
+            types: Vec::new(),
+        };
+        assert!(matches!(
+            validate(&item, &image()),
+            Err(Error::EmptyTypeList)
+        ));
+
+        let item = DragItem::Mixed {
+            files: Vec::new(),
+            data: Default::default(),
+            provider: None,
+            types: Vec::new(),
+        };
+        assert!(matches!(
+            validate(&item, &image()),
+            Err(Error::EmptyTypeList)
+        ));
+    }
+
+    #[test]
+    fn image_file_must_exist() {
+        let file = existing_file("image-file");
+        let item = DragItem::Files(vec![file.clone()]);
+        assert!(matches!(
+            validate(&item, &Image::File(missing_file())),
+            Err(Error::ImageNotFound)
+        ));
+        std::fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn rgba_image_must_match_its_size() {
+        let item = DragItem::Text("drag".into());
+        let rgba = |width, height, len| Image::Rgba {
+            width,
+            height,
+            pixels: vec![0; len],
+        };
+        assert!(validate(&item, &rgba(2, 3, 24)).is_ok());
+        assert!(matches!(
+            validate(&item, &rgba(2, 3, 23)),
+            Err(Error::InvalidImage)
+        ));
+        assert!(matches!(
+            validate(&item, &rgba(0, 3, 0)),
+            Err(Error::InvalidImage)
+        ));
+        assert!(matches!(
+            validate(&item, &rgba(2, 0, 0)),
+            Err(Error::InvalidImage)
+        ));
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn promised_files_are_unsupported() {
+        let item = DragItem::PromisedFile {
+            file_name: "promised.txt".into(),
+            writer: Box::new(|_| Ok(())),
+        };
+        assert!(matches!(
+            validate(&item, &image()),
+            Err(Error::UnsupportedOperation)
+        ));
+    }
+}
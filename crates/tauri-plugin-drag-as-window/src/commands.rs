@@ -44,7 +44,7 @@ impl Serialize for Error {
 pub struct CallbackResult {
     result: drag::DragResult,
     #[serde(rename = "cursorPos")]
-    cursor_pos: drag::CursorPosition,
+    cursor_pos: Option<drag::CursorPosition>,
 }
 
 #[command]
@@ -61,10 +61,16 @@ pub async fn on_drop<R: Runtime>(
                 .map(|f| f.starts_with(FILE_PREFIX))
                 .unwrap_or_default()
             {
-                if let Some(data) = read(path)
-                    .ok()
-                    .and_then(|bytes| serde_json::from_slice::<serde_json::Value>(&bytes).ok())
-                {
+                // the value is handed over directly while the drag is still in progress,
+                // which is never the case on Windows, where the drag ended before Tauri reports the drop
+                let local_data = drag::take_local_payload()
+                    .and_then(|payload| payload.downcast::<serde_json::Value>().ok())
+                    .map(|data| *data);
+                if let Some(data) = local_data.or_else(|| {
+                    read(path)
+                        .ok()
+                        .and_then(|bytes| serde_json::from_slice::<serde_json::Value>(&bytes).ok())
+                }) {
                     let _ = handler.send(data);
                 } else {
                     eprintln!("failed to read {}", path.display());
@@ -93,10 +99,13 @@ pub async fn drag_back<R: Runtime>(
     image_base64: String,
     on_event: Channel<CallbackResult>,
 ) -> Result<()> {
-    let data = serde_json::to_vec(&data)?;
+    // the file is written even though windows of this app receive `data` as the local payload:
+    // Tauri windows only report dropped files, so the prefixed file is what identifies the drop in `on_drop`,
+    // and whether the drop lands on another process (e.g. another instance of the app) is only known once it ended
+    let bytes = serde_json::to_vec(&data)?;
 
     let mut file = tempfile::Builder::new().prefix(FILE_PREFIX).tempfile()?;
-    file.write_all(&data)?;
+    file.write_all(&bytes)?;
     file.flush()?;
     let path = file.path().to_path_buf();
 
@@ -105,7 +114,7 @@ pub async fn drag_back<R: Runtime>(
     perform_drag(
         app,
         window,
-        DragData::Path(path),
+        DragData::Path(path, data),
         image_base64,
         on_event,
         move || {
@@ -120,7 +129,7 @@ pub async fn drag_back<R: Runtime>(
 }
 
 enum DragData {
-    Path(PathBuf),
+    Path(PathBuf, serde_json::Value),
     Data,
 }
 
@@ -143,35 +152,40 @@ fn perform_drag<R: Runtime, F: Fn() + Send + Sync + 'static>(
     );
 
     app.run_on_main_thread(move || {
-        #[cfg(target_os = "linux")]
-        let raw_window = window.gtk_window();
-        #[cfg(not(target_os = "linux"))]
-        let raw_window = tauri::Result::Ok(window.clone());
-
-        let r = match raw_window {
-            Ok(w) => drag::start_drag(
-                &w,
-                match data {
-                    DragData::Path(p) => drag::DragItem::Files(vec![p]),
-                    DragData::Data => drag::DragItem::Data {
-                        provider: Box::new(|_type| Some(Vec::new())),
-                        types: vec![window.config().identifier.clone()],
-                    },
+        let (item, payload) = match data {
+            DragData::Path(p, data) => (
+                drag::DragItem::Files(vec![p]),
+                Some(Box::new(data) as Box<dyn std::any::Any + Send>),
+            ),
+            DragData::Data => (
+                drag::DragItem::Data {
+                    provider: Box::new(|_type| Some(Vec::new())),
+                    types: vec![window.config().identifier.clone()],
                 },
-                image,
-                move |result, cursor_pos| {
-                    let callback_result = CallbackResult { result, cursor_pos };
-                    let _ = on_event.send(callback_result);
-
-                    handler();
-                },
-                drag::Options {
-                    skip_animatation_on_cancel_or_failure: true,
-                },
-            )
-            .map_err(Into::into),
-            Err(e) => Err(e.into()),
+                None,
+            ),
         };
+        let r = drag::start_drag(
+            &window,
+            item,
+            image,
+            move |outcome| {
+                let callback_result = CallbackResult {
+                    result: outcome.result,
+                    cursor_pos: outcome.cursor_position,
+                };
+                let _ = on_event.send(callback_result);
+
+                handler();
+            },
+            drag::Options {
+                skip_animatation_on_cancel_or_failure: true,
+                payload,
+                ..Default::default()
+            },
+        )
+        .map(|_session| ())
+        .map_err(Into::into);
         tx.send(r).unwrap();
     })?;
 
@@ -73,7 +73,7 @@ pub enum SharedData {
 pub struct CallbackResult {
     result: drag::DragResult,
     #[serde(rename = "cursorPos")]
-    cursor_pos: drag::CursorPosition,
+    cursor_pos: Option<drag::CursorPosition>,
 }
 
 #[command]
@@ -95,34 +95,30 @@ pub async fn start_drag<R: Runtime>(
     };
 
     app.run_on_main_thread(move || {
-        #[cfg(target_os = "linux")]
-        let raw_window = window.gtk_window();
-        #[cfg(not(target_os = "linux"))]
-        let raw_window = tauri::Result::Ok(window.clone());
-
-        let r = match raw_window {
-            Ok(w) => drag::start_drag(
-                &w,
-                match item {
-                    DragItem::Files(f) => drag::DragItem::Files(f),
-                    DragItem::Data { data, types } => drag::DragItem::Data {
-                        provider: Box::new(move |data_type| match &data {
-                            SharedData::Fixed(d) => Some(d.as_bytes().to_vec()),
-                            SharedData::Map(m) => m.get(data_type).map(|d| d.as_bytes().to_vec()),
-                        }),
-                        types,
-                    },
-                },
-                image,
-                move |result, cursor_pos| {
-                    let callback_result = CallbackResult { result, cursor_pos };
-                    let _ = on_event.send(callback_result);
+        let r = drag::start_drag(
+            &window,
+            match item {
+                DragItem::Files(f) => drag::DragItem::Files(f),
+                DragItem::Data { data, types } => drag::DragItem::Data {
+                    provider: Box::new(move |data_type| match &data {
+                        SharedData::Fixed(d) => Some(d.as_bytes().to_vec()),
+                        SharedData::Map(m) => m.get(data_type).map(|d| d.as_bytes().to_vec()),
+                    }),
+                    types,
                 },
-                Default::default(),
-            )
-            .map_err(Into::into),
-            Err(e) => Err(e.into()),
-        };
+            },
+            image,
+            move |outcome| {
+                let callback_result = CallbackResult {
+                    result: outcome.result,
+                    cursor_pos: outcome.cursor_position,
+                };
+                let _ = on_event.send(callback_result);
+            },
+            Default::default(),
+        )
+        .map(|_session| ())
+        .map_err(Into::into);
         tx.send(r).unwrap();
     })?;
 
@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-License-Identifier: MIT
 
-use drag::{start_drag, CursorPosition, DragItem, DragResult, Image};
+use drag::{start_drag, DragItem, DragOutcome, Image};
 use tao::{
     dpi::LogicalSize,
     event::{ElementState, Event, MouseButton, StartCause, WindowEvent},
@@ -42,21 +42,12 @@ fn main() {
                 ..
             } => {
                 start_drag(
-                    #[cfg(target_os = "linux")]
-                    {
-                        use tao::platform::unix::WindowExtUnix;
-                        window.gtk_window()
-                    },
-                    #[cfg(not(target_os = "linux"))]
                     &window,
                     DragItem::Files(vec![std::fs::canonicalize("./examples/icon.png").unwrap()]),
                     Image::Raw(include_bytes!("../../icon.png").to_vec()),
                     // Image::File("./examples/icon.png".into()),
-                    |result: DragResult, cursor_pos: CursorPosition| {
-                        println!(
-                            "--> Drop Result: [{:?}], Cursor Pos:[{:?}]",
-                            result, cursor_pos
-                        );
+                    |outcome: DragOutcome| {
+                        println!("--> Drop Outcome: [{:?}]", outcome);
                     },
                     Default::default(),
                 )
@@ -2,14 +2,8 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-License-Identifier: MIT
 
-#[cfg(target_os = "linux")]
 fn main() {
-    eprintln!("This example is not supported on Linux");
-}
-
-#[cfg(not(target_os = "linux"))]
-fn main() {
-    use drag::{start_drag, CursorPosition, DragItem, DragResult, Image};
+    use drag::{start_drag, DragItem, DragOutcome, Image};
     use std::collections::HashMap;
 
     use winit::{
@@ -23,7 +17,8 @@ fn main() {
     let event_loop = EventLoop::new().unwrap();
 
     struct Application {
-        windows: HashMap<WindowId, Window>,
+        // the prepared handle is dropped before its window
+        windows: HashMap<WindowId, (drag::PreparedWindow, Window)>,
     }
 
     impl ApplicationHandler for Application {
@@ -35,8 +30,9 @@ fn main() {
                         .with_title("Drag Example"),
                 )
                 .unwrap();
+            let prepared = drag::prepare(&window).unwrap();
 
-            self.windows.insert(window.id(), window);
+            self.windows.insert(window.id(), (prepared, window));
         }
 
         fn window_event(
@@ -62,15 +58,12 @@ fn main() {
             } = event
             {
                 start_drag(
-                    &self.windows.values().next().unwrap(),
+                    &self.windows.values().next().unwrap().1,
                     DragItem::Files(vec![std::fs::canonicalize("./examples/icon.png").unwrap()]),
                     Image::Raw(include_bytes!("../../icon.png").to_vec()),
                     // Image::File("./examples/icon.png".into()),
-                    |result: DragResult, cursor_pos: CursorPosition| {
-                        println!(
-                            "--> Drop Result: [{:?}], Cursor Pos:[{:?}]",
-                            result, cursor_pos
-                        );
+                    |outcome: DragOutcome| {
+                        println!("--> Drop Outcome: [{:?}]", outcome);
                     },
                     Default::default(),
                 )
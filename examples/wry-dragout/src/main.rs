@@ -3,7 +3,7 @@
 // SPDX-License-Identifier: MIT
 
 use base64::Engine;
-use drag::{start_drag, CursorPosition, DragItem, DragResult, Image};
+use drag::{start_drag, CursorPosition, DragItem, DragOutcome, Image};
 use serde::{Deserialize, Deserializer};
 use std::collections::HashMap;
 use std::path::PathBuf;
@@ -26,7 +26,7 @@ enum UserEvent {
     PopulateElement(WindowId, String),
     RemoveElement(WindowId, String),
     CloseWindow(WindowId),
-    NewWindow(CursorPosition, String),
+    NewWindow(Option<CursorPosition>, String),
 }
 
 #[derive(Debug, Deserialize)]
@@ -88,7 +88,7 @@ fn main() -> wry::Result<()> {
                     format!("Window {}", webviews.len() + 1),
                     event_loop,
                     proxy.clone(),
-                    Some(cursor_pos),
+                    cursor_pos,
                     item,
                 )
                 .unwrap();
@@ -128,24 +128,18 @@ fn main() -> wry::Result<()> {
                 };
 
                 start_drag(
-                    #[cfg(target_os = "linux")]
-                    {
-                        use tao::platform::unix::WindowExtUnix;
-                        window.gtk_window()
-                    },
-                    #[cfg(not(target_os = "linux"))]
                     &window,
                     DragItem::Data {
                         provider: Box::new(|_| Some(Vec::new())),
                         types: vec!["com.app.myapp.v2".into()],
                     },
                     icon,
-                    move |result: DragResult, cursor_pos: CursorPosition| {
-                        println!(
-                            "--> Drop Result: [{:?}], Cursor Pos:[{:?}]",
-                            result, cursor_pos
-                        );
-                        let _ = proxy.send_event(UserEvent::NewWindow(cursor_pos, item.clone()));
+                    move |outcome: DragOutcome| {
+                        println!("--> Drop Outcome: [{:?}]", outcome);
+                        let _ = proxy.send_event(UserEvent::NewWindow(
+                            outcome.cursor_position,
+                            item.clone(),
+                        ));
                         let _ = proxy.send_event(UserEvent::RemoveElement(id, item.clone()));
                     },
                     Default::default(),
@@ -167,20 +161,11 @@ fn main() -> wry::Result<()> {
                 let dummy_path = "./examples/wry-dragout/dummy/".to_owned() + &item;
                 paths.push(PathBuf::from(dummy_path).canonicalize().unwrap());
                 start_drag(
-                    #[cfg(target_os = "linux")]
-                    {
-                        use tao::platform::unix::WindowExtUnix;
-                        window.gtk_window()
-                    },
-                    #[cfg(not(target_os = "linux"))]
                     &window,
                     DragItem::Files(paths),
                     icon,
-                    move |result: DragResult, cursor_pos: CursorPosition| {
-                        println!(
-                            "--> Drop Result: [{:?}], Cursor Pos:[{:?}]",
-                            result, cursor_pos
-                        );
+                    move |outcome: DragOutcome| {
+                        println!("--> Drop Outcome: [{:?}]", outcome);
                         let _ = proxy.send_event(UserEvent::CloseWindow(id));
                     },
                     Default::default(),
@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-License-Identifier: MIT
 
-use drag::{start_drag, CursorPosition, DragItem, DragResult, Image};
+use drag::{start_drag, DragItem, DragOutcome, Image};
 use tao::{
     dpi::LogicalSize,
     event::{Event, StartCause, WindowEvent},
@@ -84,12 +84,6 @@ fn main() -> wry::Result<()> {
             Event::UserEvent(e) => match e {
                 UserEvent::StartDrag => {
                     start_drag(
-                        #[cfg(target_os = "linux")]
-                        {
-                            use tao::platform::unix::WindowExtUnix;
-                            window.gtk_window()
-                        },
-                        #[cfg(not(target_os = "linux"))]
                         &window,
                         DragItem::Files(vec![
                             std::fs::canonicalize("./examples/icon.png").unwrap(),
@@ -97,8 +91,8 @@ fn main() -> wry::Result<()> {
                         ]),
                         Image::Raw(include_bytes!("../../icon.png").to_vec()),
                         // Image::File("./examples/icon.png".into()),
-                        |result: DragResult, _cursor_pos: CursorPosition| {
-                            println!("--> Drop Result: [{:?}]", result);
+                        |outcome: DragOutcome| {
+                            println!("--> Drop Result: [{:?}]", outcome.result);
                         },
                         Default::default(),
                     )
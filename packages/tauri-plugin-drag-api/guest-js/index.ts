@@ -4,7 +4,17 @@ export type DragItem =
   | string[]
   | { data: string | Record<string, string>; types: string[] };
 
-export type DragResult = "Dropped" | "Cancelled";
+export type DragOperation = "Copy" | "Move" | "Link" | "Ask";
+
+export type CancelReason =
+  | "NoTarget"
+  | "UserCancelled"
+  | "TimeoutExpired"
+  | "GrabBroken"
+  | "Programmatic"
+  | "Error";
+
+export type DragResult = { Dropped: DragOperation } | { Cancel: CancelReason };
 
 /**
  * Logical position of the cursor.
@@ -21,7 +31,7 @@ export interface Options {
 
 export interface CallbackPayload {
   result: DragResult;
-  cursorPos: CursorPosition;
+  cursorPos: CursorPosition | null;
 }
 
 /**
@@ -1,7 +1,17 @@
 import { Channel, invoke } from "@tauri-apps/api/core";
 import html2canvas from "html2canvas";
 
-type DragResult = "Dropped" | "Cancelled";
+type DragOperation = "Copy" | "Move" | "Link" | "Ask";
+
+type CancelReason =
+  | "NoTarget"
+  | "UserCancelled"
+  | "TimeoutExpired"
+  | "GrabBroken"
+  | "Programmatic"
+  | "Error";
+
+type DragResult = { Dropped: DragOperation } | { Cancel: CancelReason };
 
 /**
  * Logical position of the cursor.
@@ -13,11 +23,11 @@ export interface CursorPosition {
 
 interface RawCallbackPayload {
   result: DragResult;
-  cursorPos: CursorPosition;
+  cursorPos: CursorPosition | null;
 }
 
 export interface CallbackPayload {
-  cursorPos: CursorPosition;
+  cursorPos: CursorPosition | null;
 }
 
 /**
@@ -49,8 +59,8 @@ export async function onElementDrop(handler: (data: any) => void) {
  *     url: 'new-window',
  *     width: el.clientWidth,
  *     height: el.clientHeight + 20,
- *     x: payload.cursorPos.x,
- *     y: payload.cursorPos.y,
+ *     x: payload.cursorPos?.x,
+ *     y: payload.cursorPos?.y,
  *   });
  * });
  * ```